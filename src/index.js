@@ -1,15 +1,21 @@
 import wasmInit, { runMain } from "./pkg/image_curve.js";
 await wasmInit();
-if (import.meta.env.DEV) {
-  runMain(
-    new Worker(new URL("worker.js", import.meta.url), {
+
+function spawnWorker() {
+  if (import.meta.env.DEV) {
+    return new Worker(new URL("worker.js", import.meta.url), {
       type: "module",
-    })
-  );
-} else {
-  runMain(
-    new Worker(new URL("worker.js", import.meta.url), {
+    });
+  } else {
+    return new Worker(new URL("worker.js", import.meta.url), {
       type: "classic",
-    })
-  );
+    });
+  }
 }
+
+// leave a core each for the page and the worker driving the animation
+const poolSize = Math.max((navigator.hardwareConcurrency || 1) - 2, 0);
+runMain(
+  spawnWorker(),
+  Array.from({ length: poolSize }, () => spawnWorker())
+);
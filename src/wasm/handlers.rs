@@ -1,9 +1,8 @@
-use crate::{renderer, utils, worker, GlobalState, LocalState};
-use js_sys::{Function, Promise};
+use crate::{operators, paths, renderer, utils, velocity, worker, GlobalState, LocalState};
 use serde::{Deserialize, Serialize};
-use std::rc::Rc;
+use std::{rc::Rc, sync::atomic::Ordering};
 use wasm_bindgen::prelude::*;
-use web_sys::HtmlImageElement;
+use web_sys::{EventTarget, MouseEvent};
 
 pub fn initialize_event_listeners(global_state: Rc<GlobalState>, local_state: LocalState) {
     {
@@ -86,25 +85,223 @@ pub fn initialize_event_listeners(global_state: Rc<GlobalState>, local_state: Lo
             .unwrap();
         oninput_closure.forget();
     }
+
+    {
+        let global_state_clone = global_state.clone();
+        let onchange_closure = Closure::<dyn Fn()>::new(move || {
+            changed_stop_on_restore(&global_state_clone);
+        });
+        global_state
+            .stop_on_restore_input
+            .add_event_listener_with_callback("change", onchange_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onchange_closure.forget();
+    }
+
+    {
+        let global_state_clone = global_state.clone();
+        let onchange_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                changed_alpha_mode(&global_state_clone).await;
+            });
+        });
+        global_state
+            .alpha_mode_select
+            .add_event_listener_with_callback("change", onchange_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onchange_closure.forget();
+    }
+
+    {
+        let global_state_clone = global_state.clone();
+        let oninput_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                inputted_timeline(&global_state_clone).await;
+            });
+        });
+        global_state
+            .timeline_input
+            .add_event_listener_with_callback("input", oninput_closure.as_ref().unchecked_ref())
+            .unwrap();
+        oninput_closure.forget();
+    }
+
+    {
+        let global_state_clone = global_state.clone();
+        let onclick_closure = Closure::<dyn Fn(_)>::new(move |e: MouseEvent| {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                clicked_canvas(&global_state_clone, e).await;
+            });
+        });
+        global_state
+            .ctx
+            .canvas()
+            .unwrap()
+            .add_event_listener_with_callback("click", onclick_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onclick_closure.forget();
+    }
+
+    {
+        let global_state_clone = global_state.clone();
+        let onchange_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                changed_operator(&global_state_clone).await;
+            });
+        });
+        global_state
+            .operator_select
+            .add_event_listener_with_callback("change", onchange_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onchange_closure.forget();
+    }
+
+    {
+        let global_state_clone = global_state.clone();
+        let onchange_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                uploaded_mask(&global_state_clone).await;
+            });
+        });
+        global_state
+            .mask_upload_input
+            .add_event_listener_with_callback("change", onchange_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onchange_closure.forget();
+    }
+
+    for event in ["mousedown", "mousemove"] {
+        let global_state_clone = global_state.clone();
+        let onmouse_closure = Closure::<dyn Fn(_)>::new(move |e: MouseEvent| {
+            painted_mask(&global_state_clone, e);
+        });
+        global_state
+            .mask_ctx
+            .canvas()
+            .unwrap()
+            .add_event_listener_with_callback(event, onmouse_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onmouse_closure.forget();
+    }
+
+    {
+        let global_state_clone = global_state.clone();
+        let onmouseup_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                finished_painting_mask(&global_state_clone).await;
+            });
+        });
+        global_state
+            .mask_ctx
+            .canvas()
+            .unwrap()
+            .add_event_listener_with_callback("mouseup", onmouseup_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onmouseup_closure.forget();
+    }
+
+    {
+        let global_state_clone = global_state.clone();
+        let onclick_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                clicked_clear_mask(&global_state_clone).await;
+            });
+        });
+        local_state
+            .clear_mask_input
+            .add_event_listener_with_callback("click", onclick_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onclick_closure.forget();
+    }
+
+    let velocity_inputs: [&EventTarget; 2] = [
+        &global_state.velocity_select,
+        &global_state.velocity_segments_input,
+    ];
+    for velocity_input in velocity_inputs {
+        let global_state_clone = global_state.clone();
+        let onchange_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                changed_velocity(&global_state_clone).await;
+            });
+        });
+        velocity_input
+            .add_event_listener_with_callback("change", onchange_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onchange_closure.forget();
+    }
+
+    for channel_step_multiplier_input in &global_state.channel_step_multiplier_inputs {
+        let global_state_clone = global_state.clone();
+        let onchange_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                changed_channel_step_multiplier(&global_state_clone).await;
+            });
+        });
+        channel_step_multiplier_input
+            .add_event_listener_with_callback("change", onchange_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onchange_closure.forget();
+    }
+
+    let curve_param_inputs: [&EventTarget; 13] = [
+        &global_state.curve_select,
+        &global_state.orientation_select,
+        &global_state.fit_select,
+        &global_state.spiral_direction_select,
+        &global_state.winding_select,
+        &global_state.seed_input,
+        &global_state.ring_width_input,
+        &global_state.sort_key_select,
+        &global_state.edge_threshold_input,
+        &global_state.tile_columns_input,
+        &global_state.tile_rows_input,
+        &global_state.tile_mode_select,
+        &global_state.mask_source_select,
+    ];
+    let channel_curve_selects = global_state
+        .channel_curve_selects
+        .iter()
+        .map(|channel_curve_select| channel_curve_select.as_ref());
+    for curve_param_input in curve_param_inputs.into_iter().chain(channel_curve_selects) {
+        let global_state_clone = global_state.clone();
+        let onchange_closure = Closure::<dyn Fn()>::new(move || {
+            let global_state_clone = global_state_clone.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                changed_curve(&global_state_clone).await;
+            });
+        });
+        curve_param_input
+            .add_event_listener_with_callback("change", onchange_closure.as_ref().unchecked_ref())
+            .unwrap();
+        onchange_closure.forget();
+    }
 }
 
 pub async fn uploaded_image(global_state: &GlobalState) {
     let Some(file) = global_state.upload_input.files().unwrap().get(0) else {
         return;
     };
-    let src = utils::to_base64(file).await;
-    let img = HtmlImageElement::new().unwrap();
-    img.set_src(&src);
-    let promise = Promise::new(&mut |resolve: Function, _reject: Function| {
-        img.set_onload(Some(&resolve));
-    });
-    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+    let img = utils::load_image_file(file).await;
     clicked_stop(global_state).await;
     let width = img.width();
     let height = img.height();
     let canvas = global_state.ctx.canvas().unwrap();
     canvas.set_width(width);
     canvas.set_height(height);
+    // resizing also clears the mask
+    let mask_canvas = global_state.mask_ctx.canvas().unwrap();
+    mask_canvas.set_width(width);
+    mask_canvas.set_height(height);
     global_state
         .ctx
         .draw_image_with_html_image_element_and_dw_and_dh(
@@ -120,6 +317,84 @@ pub async fn uploaded_image(global_state: &GlobalState) {
     inputted_step(global_state);
 }
 
+pub async fn uploaded_mask(global_state: &GlobalState) {
+    let Some(file) = global_state.mask_upload_input.files().unwrap().get(0) else {
+        return;
+    };
+    let img = utils::load_image_file(file).await;
+    let mask_canvas = global_state.mask_ctx.canvas().unwrap();
+    let width = mask_canvas.width() as f64;
+    let height = mask_canvas.height() as f64;
+    global_state.mask_ctx.clear_rect(0.0, 0.0, width, height);
+    global_state
+        .mask_ctx
+        .set_global_composite_operation("source-over")
+        .unwrap();
+    // the mask is stretched over the image
+    global_state
+        .mask_ctx
+        .draw_image_with_html_image_element_and_dw_and_dh(&img, 0.0, 0.0, width, height)
+        .unwrap();
+    global_state.mask_source_select.set_value("canvas");
+    changed_curve(global_state).await;
+}
+
+pub fn painted_mask(global_state: &GlobalState, e: MouseEvent) {
+    if e.buttons() & 1 == 0 {
+        return;
+    }
+    let mask_canvas = global_state.mask_ctx.canvas().unwrap();
+    let x =
+        e.offset_x() as f64 * mask_canvas.width() as f64 / mask_canvas.client_width().max(1) as f64;
+    let y = e.offset_y() as f64 * mask_canvas.height() as f64
+        / mask_canvas.client_height().max(1) as f64;
+    let radius = global_state.brush_size_input.value_as_number().max(1.0) / 2.0;
+    // holding shift erases
+    let composite_operation = if e.shift_key() {
+        "destination-out"
+    } else {
+        "source-over"
+    };
+    global_state
+        .mask_ctx
+        .set_global_composite_operation(composite_operation)
+        .unwrap();
+    global_state.mask_ctx.set_fill_style_str("white");
+    global_state.mask_ctx.begin_path();
+    global_state
+        .mask_ctx
+        .arc(x, y, radius, 0.0, std::f64::consts::TAU)
+        .unwrap();
+    global_state.mask_ctx.fill();
+}
+
+pub async fn finished_painting_mask(global_state: &GlobalState) {
+    if global_state.mask_source_select.value() != "canvas" {
+        return;
+    }
+    changed_curve(global_state).await;
+}
+
+pub async fn clicked_clear_mask(global_state: &GlobalState) {
+    let mask_canvas = global_state.mask_ctx.canvas().unwrap();
+    global_state.mask_ctx.clear_rect(
+        0.0,
+        0.0,
+        mask_canvas.width() as f64,
+        mask_canvas.height() as f64,
+    );
+    finished_painting_mask(global_state).await;
+}
+
+pub async fn changed_curve(global_state: &GlobalState) {
+    if !global_state.image_dimensions.borrow().is_loaded() {
+        return;
+    }
+    clicked_stop(global_state).await;
+    renderer::load_path(global_state).await;
+    inputted_step(global_state);
+}
+
 pub struct RequestAnimationFrameHandle {
     id: i32,
     closure: Closure<dyn FnMut()>,
@@ -135,7 +410,7 @@ impl Drop for RequestAnimationFrameHandle {
 }
 
 pub fn clicked_start(global_state: Rc<GlobalState>) {
-    if global_state.raf_handle.borrow().is_some() {
+    if global_state.raf_handle.borrow().is_some() || global_state.path_len.get().is_none() {
         return;
     }
     global_state
@@ -143,6 +418,18 @@ pub fn clicked_start(global_state: Rc<GlobalState>) {
         .post_message(&serde_wasm_bindgen::to_value(&worker::WorkerMessage::Start).unwrap())
         .unwrap();
 
+    // the worker stops by itself once the image is restored if asked to
+    let global_state_clone = global_state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let received_worker_message =
+            utils::wait_for_worker_message(&global_state_clone.worker).await;
+        if received_worker_message == MainMessage::Restored
+            && global_state_clone.raf_handle.borrow_mut().take().is_some()
+        {
+            renderer::render_pixel_data(&global_state_clone);
+        }
+    });
+
     let global_state_clone = global_state.clone();
     let render_pixel_data_loop = Closure::<dyn FnMut()>::new(move || {
         renderer::render_pixel_data(&global_state_clone);
@@ -172,7 +459,7 @@ pub async fn clicked_stop(global_state: &GlobalState) {
         return;
     }
     renderer::stop(global_state).await;
-    global_state.raf_handle.borrow_mut().take().unwrap();
+    global_state.raf_handle.borrow_mut().take();
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
@@ -180,11 +467,21 @@ pub async fn clicked_stop(global_state: &GlobalState) {
 pub enum MainMessage {
     Stepped,
     Stopped,
-    LoadedPath { path_len: u32 },
+    Restored,
+    Sought,
+    LoadedPath {
+        path_len: u32,
+        cycle_count: u32,
+    },
+    InvalidPath(paths::PathDiagnostics),
+    Located {
+        index: Option<u32>,
+        next: Option<(i32, i32)>,
+    },
 }
 
 pub async fn clicked_step(global_state: &GlobalState) {
-    if global_state.raf_handle.borrow().is_some() {
+    if global_state.raf_handle.borrow().is_some() || global_state.path_len.get().is_none() {
         return;
     }
     let received_worker_message =
@@ -198,6 +495,125 @@ pub async fn clicked_step(global_state: &GlobalState) {
     renderer::render_pixel_data(global_state);
 }
 
+pub fn changed_stop_on_restore(global_state: &GlobalState) {
+    worker::STOP_ON_RESTORE.store(
+        global_state.stop_on_restore_input.checked(),
+        Ordering::Relaxed,
+    );
+}
+
+pub async fn inputted_timeline(global_state: &GlobalState) {
+    if global_state.path_len.get().is_none() {
+        return;
+    }
+    clicked_stop(global_state).await;
+    let offset = global_state.timeline_input.value_as_number() as u32;
+    renderer::seek(offset, global_state).await;
+}
+
+pub async fn changed_alpha_mode(global_state: &GlobalState) {
+    let alpha_mode = worker::AlphaMode::from_name(&global_state.alpha_mode_select.value()).unwrap();
+    worker::ALPHA_MODE.store(alpha_mode as u8, Ordering::Relaxed);
+    inputted_step(global_state);
+    redraw_offset(global_state).await;
+}
+
+pub async fn changed_channel_step_multiplier(global_state: &GlobalState) {
+    for (multiplier, channel_step_multiplier_input) in worker::CHANNEL_STEP_MULTIPLIERS
+        .iter()
+        .zip(&global_state.channel_step_multiplier_inputs)
+    {
+        multiplier.store(
+            channel_step_multiplier_input.value_as_number() as i32,
+            Ordering::Relaxed,
+        );
+    }
+    inputted_step(global_state);
+    redraw_offset(global_state).await;
+}
+
+// every operator starts over from the original image
+pub async fn changed_operator(global_state: &GlobalState) {
+    clicked_stop(global_state).await;
+    let operator = operators::Operator::from_name(&global_state.operator_select.value()).unwrap();
+    worker::OPERATOR.store(operator as u8, Ordering::Relaxed);
+    global_state
+        .operator_status
+        .set_text_content(Some(if operator.is_invertible() {
+            "Invertible"
+        } else {
+            "Not invertible, the image may never be restored"
+        }));
+    // only a rotation has a frame for every offset
+    global_state
+        .timeline_input
+        .set_disabled(operator != operators::Operator::Rotate);
+    inputted_step(global_state);
+    if global_state.path_len.get().is_none() {
+        return;
+    }
+    renderer::seek(0, global_state).await;
+}
+
+pub async fn changed_velocity(global_state: &GlobalState) {
+    clicked_stop(global_state).await;
+    let profile =
+        velocity::VelocityProfile::from_name(&global_state.velocity_select.value()).unwrap();
+    let segment_count = global_state.velocity_segments_input.value_as_number() as u32;
+    let received_worker_message = utils::worker_operation(
+        &global_state.worker,
+        worker::WorkerMessage::SetVelocity {
+            profile,
+            segment_count,
+        },
+    )
+    .await;
+    if received_worker_message != MainMessage::Sought {
+        panic!(
+            "Expected MainMessage::Sought, got {:?}",
+            received_worker_message
+        );
+    }
+    if global_state.path_len.get().is_some() {
+        renderer::render_pixel_data(global_state);
+    }
+    inputted_step(global_state);
+}
+
+// a running animation picks up new settings on the next step
+async fn redraw_offset(global_state: &GlobalState) {
+    if global_state.raf_handle.borrow().is_some() || global_state.path_len.get().is_none() {
+        return;
+    }
+    let offset = worker::OFFSET.load(Ordering::Relaxed);
+    renderer::seek(offset, global_state).await;
+}
+
+pub async fn clicked_canvas(global_state: &GlobalState, e: MouseEvent) {
+    if global_state.raf_handle.borrow().is_some() || global_state.path_len.get().is_none() {
+        return;
+    }
+    let canvas = global_state.ctx.canvas().unwrap();
+    let x = e.offset_x() as u32 * canvas.width() / canvas.client_width().max(1) as u32;
+    let y = e.offset_y() as u32 * canvas.height() / canvas.client_height().max(1) as u32;
+    let received_worker_message =
+        utils::worker_operation(&global_state.worker, worker::WorkerMessage::Locate { x, y }).await;
+    let MainMessage::Located { index, next } = received_worker_message else {
+        panic!(
+            "Expected MainMessage::Located, got {:?}",
+            received_worker_message
+        );
+    };
+    let text = match (index, next) {
+        (Some(index), Some((next_x, next_y))) => format!(
+            "Pixel ({x}, {y}) is at index {index} of the path, the next step moves it to ({next_x}, {next_y})"
+        ),
+        (Some(index), None) => format!("Pixel ({x}, {y}) is at index {index} of the path"),
+        (None, _) => format!("Pixel ({x}, {y}) is not on the path"),
+    };
+    global_state.inspector.set_text_content(Some(&text));
+}
+
 pub fn inputted_speed(global_state: &GlobalState) {
     let new_speed_percentage = global_state.change_speed_input.value_as_number() as u32;
     renderer::change_speed(new_speed_percentage);
@@ -1,3 +1,5 @@
+#![cfg_attr(target_arch = "wasm32", feature(stdarch_wasm_atomic_wait))]
+
 use handlers::RequestAnimationFrameHandle;
 use js_sys::Array;
 use renderer::ImageDimensions;
@@ -7,12 +9,18 @@ use std::{
     rc::Rc,
 };
 use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement, Worker};
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, HtmlElement, HtmlInputElement, HtmlOptionElement,
+    HtmlSelectElement, Worker,
+};
 
 mod handlers;
+mod operators;
 mod paths;
+mod pool;
 mod renderer;
 mod utils;
+mod velocity;
 mod worker;
 
 #[wasm_bindgen(start)]
@@ -29,22 +37,65 @@ struct GlobalState {
     image_dimensions: RefCell<ImageDimensions>,
     raf_handle: RefCell<Option<RequestAnimationFrameHandle>>,
     path_len: Cell<Option<u32>>,
+    cycle_count: Cell<u32>,
     change_speed_input: HtmlInputElement,
     change_step_input: HtmlInputElement,
+    curve_select: HtmlSelectElement,
+    orientation_select: HtmlSelectElement,
+    fit_select: HtmlSelectElement,
+    spiral_direction_select: HtmlSelectElement,
+    winding_select: HtmlSelectElement,
+    seed_input: HtmlInputElement,
+    ring_width_input: HtmlInputElement,
+    sort_key_select: HtmlSelectElement,
+    edge_threshold_input: HtmlInputElement,
+    tile_columns_input: HtmlInputElement,
+    tile_rows_input: HtmlInputElement,
+    tile_mode_select: HtmlSelectElement,
+    mask_ctx: CanvasRenderingContext2d,
+    mask_source_select: HtmlSelectElement,
+    mask_upload_input: HtmlInputElement,
+    brush_size_input: HtmlInputElement,
+    path_status: HtmlElement,
+    inspector: HtmlElement,
+    timeline_input: HtmlInputElement,
+    period: HtmlElement,
+    stop_on_restore_input: HtmlInputElement,
+    restore_status: HtmlElement,
+    alpha_mode_select: HtmlSelectElement,
+    channel_curve_selects: [HtmlSelectElement; 4],
+    channel_step_multiplier_inputs: [HtmlInputElement; 4],
+    operator_select: HtmlSelectElement,
+    operator_status: HtmlElement,
+    velocity_select: HtmlSelectElement,
+    velocity_segments_input: HtmlInputElement,
 }
 
+const CHANNELS: [&str; 4] = ["red", "green", "blue", "alpha"];
+
 struct LocalState {
     step_input: HtmlInputElement,
     stop_input: HtmlInputElement,
+    clear_mask_input: HtmlInputElement,
 }
 
 #[wasm_bindgen(js_name = runMain)]
-pub fn run_main(worker: Worker) {
+pub fn run_main(worker: Worker, pool_workers: Array) {
     let worker_message = Array::new();
     worker_message.push(&wasm_bindgen::module());
     worker_message.push(&wasm_bindgen::memory());
     worker.post_message(&worker_message).unwrap();
 
+    // pool workers share the memory and help the worker with every step
+    let pool_worker_message = worker_message.clone();
+    pool_worker_message.push(&"pool".into());
+    for pool_worker in pool_workers.iter() {
+        pool_worker
+            .unchecked_into::<Worker>()
+            .post_message(&pool_worker_message)
+            .unwrap();
+    }
+
     let document = web_sys::window().unwrap().document().unwrap();
 
     let ctx = utils::get_element_by_id::<HtmlCanvasElement>(&document, "canvas")
@@ -64,9 +115,80 @@ pub fn run_main(worker: Worker) {
     let change_speed_input =
         utils::get_element_by_id::<HtmlInputElement>(&document, "change-speed");
     let change_step_input = utils::get_element_by_id::<HtmlInputElement>(&document, "change-step");
+    let curve_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "curve");
+    for (name, label) in paths::Curve::NAMES {
+        let option = HtmlOptionElement::new_with_text_and_value(label, name).unwrap();
+        curve_select.add_with_html_option_element(&option).unwrap();
+    }
+    let orientation_select =
+        utils::get_element_by_id::<HtmlSelectElement>(&document, "orientation");
+    let fit_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "fit");
+    let spiral_direction_select =
+        utils::get_element_by_id::<HtmlSelectElement>(&document, "spiral-direction");
+    let winding_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "winding");
+    let seed_input = utils::get_element_by_id::<HtmlInputElement>(&document, "seed");
+    let ring_width_input = utils::get_element_by_id::<HtmlInputElement>(&document, "ring-width");
+    let sort_key_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "sort-key");
+    let edge_threshold_input =
+        utils::get_element_by_id::<HtmlInputElement>(&document, "edge-threshold");
+    let tile_columns_input =
+        utils::get_element_by_id::<HtmlInputElement>(&document, "tile-columns");
+    let tile_rows_input = utils::get_element_by_id::<HtmlInputElement>(&document, "tile-rows");
+    let tile_mode_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "tile-mode");
+    let mask_ctx = utils::get_element_by_id::<HtmlCanvasElement>(&document, "mask")
+        .get_context("2d")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<CanvasRenderingContext2d>()
+        .unwrap();
+    let mask_source_select =
+        utils::get_element_by_id::<HtmlSelectElement>(&document, "mask-source");
+    let mask_upload_input = utils::get_element_by_id::<HtmlInputElement>(&document, "mask-upload");
+    let brush_size_input = utils::get_element_by_id::<HtmlInputElement>(&document, "brush-size");
+    let path_status = utils::get_element_by_id::<HtmlElement>(&document, "path-status");
+    let inspector = utils::get_element_by_id::<HtmlElement>(&document, "inspector");
+    let timeline_input = utils::get_element_by_id::<HtmlInputElement>(&document, "timeline");
+    let period = utils::get_element_by_id::<HtmlElement>(&document, "period");
+    let stop_on_restore_input =
+        utils::get_element_by_id::<HtmlInputElement>(&document, "stop-on-restore");
+    let restore_status = utils::get_element_by_id::<HtmlElement>(&document, "restore-status");
+    let alpha_mode_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "alpha-mode");
+    let channel_curve_selects = CHANNELS.map(|channel| {
+        let channel_curve_select =
+            utils::get_element_by_id::<HtmlSelectElement>(&document, &format!("{channel}-curve"));
+        let option = HtmlOptionElement::new_with_text_and_value("Same curve", "same").unwrap();
+        channel_curve_select
+            .add_with_html_option_element(&option)
+            .unwrap();
+        for (name, label) in paths::Curve::NAMES {
+            let option = HtmlOptionElement::new_with_text_and_value(label, name).unwrap();
+            channel_curve_select
+                .add_with_html_option_element(&option)
+                .unwrap();
+        }
+        channel_curve_select
+    });
+    let operator_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "operator");
+    for (name, label) in operators::Operator::NAMES {
+        let option = HtmlOptionElement::new_with_text_and_value(label, name).unwrap();
+        operator_select
+            .add_with_html_option_element(&option)
+            .unwrap();
+    }
+    let operator_status = utils::get_element_by_id::<HtmlElement>(&document, "operator-status");
+    let velocity_select = utils::get_element_by_id::<HtmlSelectElement>(&document, "velocity");
+    let velocity_segments_input =
+        utils::get_element_by_id::<HtmlInputElement>(&document, "velocity-segments");
+    let channel_step_multiplier_inputs = CHANNELS.map(|channel| {
+        utils::get_element_by_id::<HtmlInputElement>(
+            &document,
+            &format!("{channel}-step-multiplier"),
+        )
+    });
     let image_dimensions = Default::default();
     let raf_handle = RefCell::new(None);
     let path_len = Cell::new(None);
+    let cycle_count = Cell::new(1);
 
     let global_state = Rc::new(GlobalState {
         ctx,
@@ -76,16 +198,49 @@ pub fn run_main(worker: Worker) {
         image_dimensions,
         raf_handle,
         path_len,
+        cycle_count,
         change_speed_input,
         change_step_input,
+        curve_select,
+        orientation_select,
+        fit_select,
+        spiral_direction_select,
+        winding_select,
+        seed_input,
+        ring_width_input,
+        sort_key_select,
+        edge_threshold_input,
+        tile_columns_input,
+        tile_rows_input,
+        tile_mode_select,
+        mask_ctx,
+        mask_source_select,
+        mask_upload_input,
+        brush_size_input,
+        path_status,
+        inspector,
+        timeline_input,
+        period,
+        stop_on_restore_input,
+        restore_status,
+        alpha_mode_select,
+        channel_curve_selects,
+        channel_step_multiplier_inputs,
+        operator_select,
+        operator_status,
+        velocity_select,
+        velocity_segments_input,
     });
 
     let step_input = utils::get_element_by_id::<HtmlInputElement>(&document, "step");
     let stop_input = utils::get_element_by_id::<HtmlInputElement>(&document, "stop");
 
+    let clear_mask_input = utils::get_element_by_id::<HtmlInputElement>(&document, "clear-mask");
+
     let local_state = LocalState {
         step_input,
         stop_input,
+        clear_mask_input,
     };
 
     handlers::initialize_event_listeners(global_state, local_state);
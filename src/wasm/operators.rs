@@ -0,0 +1,171 @@
+use crate::{
+    pool,
+    worker::{self, AlphaMode},
+};
+use std::sync::atomic::Ordering;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum Operator {
+    Rotate,
+    ReverseSegments,
+    SortByLuminance,
+    SortByHue,
+    SwapBlocks,
+}
+
+impl Operator {
+    pub const NAMES: [(&'static str, &'static str); 5] = [
+        ("rotate", "Rotate along the path"),
+        ("reverse-segments", "Reverse segments"),
+        ("sort-by-luminance", "Sort segments by luminance"),
+        ("sort-by-hue", "Sort segments by hue"),
+        ("swap-blocks", "Swap alternate blocks"),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rotate" => Some(Self::Rotate),
+            "reverse-segments" => Some(Self::ReverseSegments),
+            "sort-by-luminance" => Some(Self::SortByLuminance),
+            "sort-by-hue" => Some(Self::SortByHue),
+            "swap-blocks" => Some(Self::SwapBlocks),
+            _ => None,
+        }
+    }
+
+    pub fn load() -> Self {
+        match worker::OPERATOR.load(Ordering::Relaxed) {
+            1 => Self::ReverseSegments,
+            2 => Self::SortByLuminance,
+            3 => Self::SortByHue,
+            4 => Self::SwapBlocks,
+            _ => Self::Rotate,
+        }
+    }
+
+    // sorting forgets the order the pixels were in, every other operator
+    // only moves them around and can be undone
+    pub fn is_invertible(self) -> bool {
+        !matches!(self, Self::SortByLuminance | Self::SortByHue)
+    }
+
+    // rotating is a gather from the original pixels instead
+    pub fn rearrangement(self) -> Option<Rearrangement> {
+        match self {
+            Self::Rotate => None,
+            Self::ReverseSegments => Some(Rearrangement::ReverseSegments),
+            Self::SortByLuminance => Some(Rearrangement::SortByLuminance),
+            Self::SortByHue => Some(Rearrangement::SortByHue),
+            Self::SwapBlocks => Some(Rearrangement::SwapBlocks),
+        }
+    }
+}
+
+// the operators that rearrange the pixels of the frame in place
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rearrangement {
+    ReverseSegments,
+    SortByLuminance,
+    SortByHue,
+    SwapBlocks,
+}
+
+impl Rearrangement {
+    // how many indices of the path one step rearranges together
+    pub fn segment_len(self, steps: usize) -> usize {
+        match self {
+            Self::SwapBlocks => steps * 2,
+            _ => steps,
+        }
+    }
+
+    fn rearrange(self, segment: &mut [u32], steps: usize) {
+        match self {
+            Self::ReverseSegments => segment.reverse(),
+            Self::SortByLuminance => segment.sort_by_key(|&pixel| luminance(pixel)),
+            Self::SortByHue => segment.sort_by_key(|&pixel| hue(pixel)),
+            Self::SwapBlocks => segment.rotate_left(steps.min(segment.len())),
+        }
+    }
+
+    // rearranges the pixels of every segment of the path in place, the first
+    // segment starting at index `phase`. segments never overlap, so they are
+    // split between the pool workers
+    pub fn apply(
+        self,
+        path: &[u32],
+        phase: usize,
+        steps: usize,
+        pixel_data_ptr: usize,
+        alpha_mode: AlphaMode,
+    ) {
+        let path_len = path.len();
+        let segment_len = self.segment_len(steps).min(path_len);
+        let segments = path_len.div_ceil(segment_len);
+        pool::run(&|chunk, chunks| {
+            let pixel_data_ptr = pixel_data_ptr as *mut u32;
+            let mut segment = Vec::with_capacity(segment_len);
+            for segment_index in pool::chunk_range(segments, chunk, chunks) {
+                let start = phase + segment_index * segment_len;
+                let end = (start + segment_len).min(phase + path_len);
+                let pixels = (start..end).map(|index| path[index % path_len] as usize);
+                segment.clear();
+                segment.extend(
+                    pixels
+                        .clone()
+                        .map(|pixel| unsafe { pixel_data_ptr.add(pixel).read_unaligned() }),
+                );
+                self.rearrange(&mut segment, steps);
+                for (pixel, &value) in pixels.zip(&segment) {
+                    unsafe {
+                        let pixel_ptr = pixel_data_ptr.add(pixel);
+                        // fixed alpha stays with the position
+                        let value = match alpha_mode {
+                            AlphaMode::Fixed => {
+                                (pixel_ptr.read_unaligned() & worker::ALPHA_MASK)
+                                    | (value & !worker::ALPHA_MASK)
+                            }
+                            AlphaMode::Move | AlphaMode::Drop => value,
+                        };
+                        pixel_ptr.write_unaligned(value);
+                    }
+                }
+            }
+        });
+    }
+}
+
+// rec. 709 weights out of 256
+pub fn luminance(pixel: u32) -> u32 {
+    let [r, g, b, _] = pixel.to_le_bytes().map(u32::from);
+    54 * r + 183 * g + 19 * b
+}
+
+// out of 255, greys have none
+pub fn saturation(pixel: u32) -> u32 {
+    let [r, g, b, _] = pixel.to_le_bytes().map(u32::from);
+    let max = r.max(g).max(b);
+    if max == 0 {
+        return 0;
+    }
+    (max - r.min(g).min(b)) * 255 / max
+}
+
+// in 256ths of a sixth of the colour wheel, greys come first
+pub fn hue(pixel: u32) -> u32 {
+    let [r, g, b, _] = pixel.to_le_bytes().map(i32::from);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta == 0 {
+        return 0;
+    }
+    let hue = if max == r {
+        (g - b) * 256 / delta
+    } else if max == g {
+        2 * 256 + (b - r) * 256 / delta
+    } else {
+        4 * 256 + (r - g) * 256 / delta
+    };
+    hue.rem_euclid(6 * 256) as u32
+}
@@ -1,106 +1,1713 @@
-use crate::renderer::Point;
+use crate::{operators, renderer::Point, velocity::VelocitySegment};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
 
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", content = "params")]
+pub enum Curve {
+    Gilbert {
+        orientation: Orientation,
+    },
+    GilbertLoop,
+    Hilbert {
+        fit: Fit,
+    },
+    Moore {
+        fit: Fit,
+    },
+    Peano {
+        fit: Fit,
+    },
+    PeanoMeander {
+        fit: Fit,
+    },
+    ZOrder {
+        orientation: Orientation,
+    },
+    Raster {
+        orientation: Orientation,
+    },
+    Serpentine {
+        orientation: Orientation,
+    },
+    Spiral {
+        direction: SpiralDirection,
+        winding: Winding,
+    },
+    CircularSpiral {
+        direction: SpiralDirection,
+        winding: Winding,
+    },
+    Shuffle {
+        seed: u32,
+    },
+    Maze {
+        seed: u32,
+    },
+    Shift,
+    Rings {
+        ring_width: u32,
+        winding: Winding,
+    },
+    Sorted {
+        key: SortKey,
+        orientation: Orientation,
+    },
+    Edges {
+        threshold: u32,
+        orientation: Orientation,
+    },
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum SortKey {
+    #[default]
+    Luminance,
+    Hue,
+    Saturation,
+    Red,
+    Green,
+    Blue,
+}
+
+impl SortKey {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "luminance" => Some(Self::Luminance),
+            "hue" => Some(Self::Hue),
+            "saturation" => Some(Self::Saturation),
+            "red" => Some(Self::Red),
+            "green" => Some(Self::Green),
+            "blue" => Some(Self::Blue),
+            _ => None,
+        }
+    }
+
+    fn value(self, pixel: u32) -> u32 {
+        match self {
+            Self::Luminance => operators::luminance(pixel),
+            Self::Hue => operators::hue(pixel),
+            Self::Saturation => operators::saturation(pixel),
+            Self::Red => pixel & 0xff,
+            Self::Green => pixel >> 8 & 0xff,
+            Self::Blue => pixel >> 16 & 0xff,
+        }
+    }
+}
+
+// the pixels a curve is laid over, starting at `x` and `y` of the image, for
+// the curves that are ordered by colour
+#[derive(Copy, Clone)]
+pub struct Pixels<'a> {
+    pixel_data: &'a [u8],
+    width: u32,
+    x: u32,
+    y: u32,
+}
+
+impl<'a> Pixels<'a> {
+    pub fn new(pixel_data: &'a [u8], width: u32) -> Self {
+        Self {
+            pixel_data,
+            width,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    fn offset(self, x: u32, y: u32) -> Self {
+        Self {
+            x: self.x + x,
+            y: self.y + y,
+            ..self
+        }
+    }
+
+    fn at(&self, x: u32, y: u32) -> u32 {
+        let pixel = ((self.y + y) * self.width + self.x + x) as usize * 4;
+        u32::from_le_bytes(self.pixel_data[pixel..pixel + 4].try_into().unwrap())
+    }
+}
+
+// the path is a set of disjoint cycles laid end to end, every one of them
+// turns `revolutions` times while the offset goes once around the path, the
+// other way when negative, starting `phase` indices along
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cycle {
+    pub len: u32,
+    pub revolutions: i64,
+    pub phase: u32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum TileMode {
+    #[default]
+    Together,
+    Phase,
+    Speed,
+}
+
+impl TileMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "together" => Some(Self::Together),
+            "phase" => Some(Self::Phase),
+            "speed" => Some(Self::Speed),
+            _ => None,
+        }
+    }
+}
+
+// lays a separate curve inside every tile of a grid, the tiles start at a
+// random phase or turn at a random speed of their own depending on `mode`
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Tiling {
+    pub columns: u32,
+    pub rows: u32,
+    pub mode: TileMode,
+    pub seed: u32,
+}
+
+impl Tiling {
+    // the top left corner and size of every tile, row by row
+    fn tiles(&self, w: u32, h: u32) -> impl Iterator<Item = (u32, u32, u32, u32)> {
+        let columns = self.columns.clamp(1, w.max(1));
+        let rows = self.rows.clamp(1, h.max(1));
+        (0..rows).flat_map(move |row| {
+            (0..columns).map(move |column| {
+                let x = w * column / columns;
+                let y = h * row / rows;
+                (
+                    x,
+                    y,
+                    w * (column + 1) / columns - x,
+                    h * (row + 1) / rows - y,
+                )
+            })
+        })
+    }
+
+    pub fn points(&self, curve: &Curve, w: u32, h: u32, pixels: Pixels) -> Vec<Point> {
+        // a single tile is the curve itself, no need to collect it twice
+        if self.tiles(w, h).nth(1).is_none() {
+            return curve.points(w, h, pixels);
+        }
+        self.tiles(w, h)
+            .flat_map(|(x, y, tile_w, tile_h)| {
+                curve
+                    .points(tile_w, tile_h, pixels.offset(x, y))
+                    .into_iter()
+                    .map(move |Point(px, py)| Point(px + x as i32, py + y as i32))
+            })
+            .collect()
+    }
+
+    pub fn cycles(&self, curve: &Curve, points: &[Point], w: u32, h: u32) -> Vec<Cycle> {
+        let mut rng = SplitMix64::new(self.seed);
+        let mut cycles = Vec::new();
+        let mut start = 0;
+        for (x, y, tile_w, tile_h) in self.tiles(w, h) {
+            let len = (tile_w * tile_h) as usize;
+            // the top left tile is already where its curve starts
+            let translated: Vec<_>;
+            let tile_points = if x == 0 && y == 0 {
+                &points[start..start + len]
+            } else {
+                translated = points[start..start + len]
+                    .iter()
+                    .map(|&Point(px, py)| Point(px - x as i32, py - y as i32))
+                    .collect();
+                &translated
+            };
+            start += len;
+            let (phase, speed) = match self.mode {
+                TileMode::Together => (0, 1),
+                TileMode::Phase => (rng.next_u64(), 1),
+                // between three times as fast either way
+                TileMode::Speed => {
+                    let speed = (rng.next_u64() % 6) as i64 - 3;
+                    (0, if speed >= 0 { speed + 1 } else { speed })
+                }
+            };
+            cycles.extend(
+                curve
+                    .cycles(tile_points, tile_w, tile_h)
+                    .into_iter()
+                    .map(|cycle| Cycle {
+                        revolutions: cycle.revolutions * speed,
+                        phase: (phase % cycle.len as u64) as u32,
+                        ..cycle
+                    }),
+            );
+        }
+        cycles
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum Orientation {
+    #[default]
+    Auto,
+    Horizontal,
+    Vertical,
+}
+
+impl Orientation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Self::Auto),
+            "horizontal" => Some(Self::Horizontal),
+            "vertical" => Some(Self::Vertical),
+            _ => None,
+        }
+    }
+
+    fn is_horizontal(self, w: u32, h: u32) -> bool {
+        match self {
+            Self::Auto => w >= h,
+            Self::Horizontal => true,
+            Self::Vertical => false,
+        }
+    }
+}
+
+// how a curve that only exists for power-of-two squares is laid over a
+// rectangle, either way the pixels outside the image are skipped
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum Fit {
+    // the curve starts at the top left corner of the image and whatever
+    // hangs over the right or bottom edge is cropped off
+    #[default]
+    Crop,
+    // the image is centered inside the padded square
+    Pad,
+}
+
+impl Fit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "crop" => Some(Self::Crop),
+            "pad" => Some(Self::Pad),
+            _ => None,
+        }
+    }
+
+    fn offset(self, side: u32, w: u32, h: u32) -> (u32, u32) {
+        match self {
+            Self::Crop => (0, 0),
+            Self::Pad => ((side - w) / 2, (side - h) / 2),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum SpiralDirection {
+    #[default]
+    Inward,
+    Outward,
+}
+
+impl SpiralDirection {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "inward" => Some(Self::Inward),
+            "outward" => Some(Self::Outward),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum Winding {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Winding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clockwise" => Some(Self::Clockwise),
+            "counter-clockwise" => Some(Self::CounterClockwise),
+            _ => None,
+        }
+    }
+
+    // walking a spiral backwards also turns it the other way
+    fn reversed(self) -> Self {
+        match self {
+            Self::Clockwise => Self::CounterClockwise,
+            Self::CounterClockwise => Self::Clockwise,
+        }
+    }
+}
+
+// parameters read from the page, each curve only picks the ones it uses
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CurveParams {
+    pub orientation: Orientation,
+    pub fit: Fit,
+    pub spiral_direction: SpiralDirection,
+    pub winding: Winding,
+    pub seed: u32,
+    pub ring_width: u32,
+    pub sort_key: SortKey,
+    pub edge_threshold: u32,
+}
+
+impl Curve {
+    pub const NAMES: [(&'static str, &'static str); 17] = [
+        ("gilbert", "Generalized Hilbert"),
+        ("gilbert-loop", "Generalized Hilbert loop"),
+        ("hilbert", "Hilbert"),
+        ("moore", "Moore"),
+        ("peano", "Peano"),
+        ("peano-meander", "Peano meander"),
+        ("z-order", "Z-order (Lebesgue)"),
+        ("raster", "Raster"),
+        ("serpentine", "Serpentine"),
+        ("spiral", "Spiral"),
+        ("circular-spiral", "Circular spiral"),
+        ("shuffle", "Random shuffle"),
+        ("maze", "Random maze"),
+        ("shift", "Shift"),
+        ("rings", "Concentric rings"),
+        ("sorted", "Sorted by colour"),
+        ("edges", "Following edges"),
+    ];
+
+    pub fn from_name(name: &str, params: CurveParams) -> Option<Self> {
+        match name {
+            "gilbert" => Some(Self::Gilbert {
+                orientation: params.orientation,
+            }),
+            "gilbert-loop" => Some(Self::GilbertLoop),
+            "hilbert" => Some(Self::Hilbert { fit: params.fit }),
+            "moore" => Some(Self::Moore { fit: params.fit }),
+            "peano" => Some(Self::Peano { fit: params.fit }),
+            "peano-meander" => Some(Self::PeanoMeander { fit: params.fit }),
+            "z-order" => Some(Self::ZOrder {
+                orientation: params.orientation,
+            }),
+            "raster" => Some(Self::Raster {
+                orientation: params.orientation,
+            }),
+            "serpentine" => Some(Self::Serpentine {
+                orientation: params.orientation,
+            }),
+            "spiral" => Some(Self::Spiral {
+                direction: params.spiral_direction,
+                winding: params.winding,
+            }),
+            "circular-spiral" => Some(Self::CircularSpiral {
+                direction: params.spiral_direction,
+                winding: params.winding,
+            }),
+            "shuffle" => Some(Self::Shuffle { seed: params.seed }),
+            "maze" => Some(Self::Maze { seed: params.seed }),
+            "shift" => Some(Self::Shift),
+            "rings" => Some(Self::Rings {
+                ring_width: params.ring_width.max(1),
+                winding: params.winding,
+            }),
+            "sorted" => Some(Self::Sorted {
+                key: params.sort_key,
+                orientation: params.orientation,
+            }),
+            "edges" => Some(Self::Edges {
+                threshold: params.edge_threshold,
+                orientation: params.orientation,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn points(&self, w: u32, h: u32, pixels: Pixels) -> Vec<Point> {
+        match *self {
+            Self::Gilbert { orientation } => gilbert_points(w, h, orientation),
+            Self::GilbertLoop => gilbert_loop(w, h),
+            Self::Hilbert { .. }
+            | Self::Moore { .. }
+            | Self::Peano { .. }
+            | Self::PeanoMeander { .. }
+            | Self::ZOrder { .. } => sorted_by_key(w, h, |x, y| self.xy2d(x, y, w, h).unwrap()),
+            Self::Raster { orientation } => {
+                let horizontal = orientation.is_horizontal(w, h);
+                (0..w * h)
+                    .map(|idx| scan_d2xy(idx, w, h, horizontal, false))
+                    .collect()
+            }
+            Self::Serpentine { orientation } => {
+                let horizontal = orientation.is_horizontal(w, h);
+                (0..w * h)
+                    .map(|idx| scan_d2xy(idx, w, h, horizontal, true))
+                    .collect()
+            }
+            Self::Spiral { direction, winding } => {
+                if direction == SpiralDirection::Outward {
+                    let mut points = spiral(w, h, winding.reversed());
+                    points.reverse();
+                    points
+                } else {
+                    spiral(w, h, winding)
+                }
+            }
+            Self::CircularSpiral { direction, winding } => {
+                if direction == SpiralDirection::Inward {
+                    let winding = winding.reversed();
+                    let mut points =
+                        sorted_by_key(w, h, |x, y| circular_spiral_key(x, y, w, h, winding));
+                    points.reverse();
+                    points
+                } else {
+                    sorted_by_key(w, h, |x, y| circular_spiral_key(x, y, w, h, winding))
+                }
+            }
+            Self::Shuffle { seed } => {
+                let mut points = Self::Raster {
+                    orientation: Orientation::Horizontal,
+                }
+                .points(w, h, pixels);
+                SplitMix64::new(seed).shuffle(&mut points);
+                points
+            }
+            Self::Maze { seed } => maze(w, h, seed),
+            Self::Shift => (0..w * h).map(|idx| shift(idx, w, h)).collect(),
+            Self::Rings { ring_width, .. } => {
+                sorted_by_key(w, h, |x, y| rings_key(x, y, w, h, ring_width))
+            }
+            // pixels of the same colour keep the order of the gilbert curve
+            Self::Sorted { key, orientation } => sorted_by_key(w, h, |x, y| {
+                (key.value(pixels.at(x, y)) as u64) << 32
+                    | gilbert_xy2d(x, y, w, h, orientation) as u64
+            }),
+            Self::Edges {
+                threshold,
+                orientation,
+            } => edges(w, h, pixels, threshold, orientation),
+        }
+    }
+
+    // how the points of the curve split into cycles, the rings of the rings
+    // curve turn in alternating directions starting with `winding` in the
+    // middle. every other curve is a single cycle
+    pub fn cycles(&self, points: &[Point], w: u32, h: u32) -> Vec<Cycle> {
+        let Self::Rings {
+            ring_width,
+            winding,
+        } = *self
+        else {
+            return vec![Cycle {
+                len: points.len() as u32,
+                revolutions: 1,
+                phase: 0,
+            }];
+        };
+        let mut cycles: Vec<Cycle> = Vec::new();
+        let mut previous_ring = None;
+        for &Point(x, y) in points {
+            let ring = rings_key(x as u32, y as u32, w, h, ring_width) >> 32;
+            if previous_ring == Some(ring) {
+                cycles.last_mut().unwrap().len += 1;
+                continue;
+            }
+            previous_ring = Some(ring);
+            // rings are ordered clockwise and a rotation moves pixels back
+            // along the path, so turning clockwise is going the other way
+            let clockwise = (ring & 1 == 0) == (winding == Winding::Clockwise);
+            cycles.push(Cycle {
+                len: 1,
+                revolutions: if clockwise { -1 } else { 1 },
+                phase: 0,
+            });
+        }
+        cycles
+    }
+
+    // the index of a pixel along the curve without generating the path, for
+    // the curves that have a closed form. curves cut out of a bigger square
+    // count the indices of the pixels outside of the image too
+    pub fn xy2d(&self, x: u32, y: u32, w: u32, h: u32) -> Option<u64> {
+        match *self {
+            Self::Gilbert { orientation } => Some(gilbert_xy2d(x, y, w, h, orientation) as u64),
+            Self::Hilbert { fit } => {
+                let side = w.max(h).next_power_of_two();
+                let (dx, dy) = fit.offset(side, w, h);
+                Some(hilbert_xy2d(x + dx, y + dy, side))
+            }
+            Self::Moore { fit } => {
+                let side = w.max(h).next_power_of_two().max(2);
+                let (dx, dy) = fit.offset(side, w, h);
+                Some(moore_xy2d(x + dx, y + dy, side))
+            }
+            Self::Peano { fit } | Self::PeanoMeander { fit } => {
+                let pattern = if let Self::Peano { .. } = self {
+                    &PEANO
+                } else {
+                    &PEANO_MEANDER
+                };
+                let side = peano_side(w, h);
+                let (dx, dy) = fit.offset(side, w, h);
+                Some(peano_xy2d(pattern, x + dx, y + dy, side))
+            }
+            Self::ZOrder { orientation } => {
+                if orientation.is_horizontal(w, h) {
+                    Some(morton_xy2d(x, y))
+                } else {
+                    Some(morton_xy2d(y, x))
+                }
+            }
+            Self::Raster { orientation } | Self::Serpentine { orientation } => {
+                let (line_len, line, mut along) = if orientation.is_horizontal(w, h) {
+                    (w, y, x)
+                } else {
+                    (h, x, y)
+                };
+                if matches!(self, Self::Serpentine { .. }) && line % 2 == 1 {
+                    along = line_len - 1 - along;
+                }
+                Some(line as u64 * line_len as u64 + along as u64)
+            }
+            _ => None,
+        }
+    }
+}
+
+// looks up both directions between the pixels of the image and their index
+// along a validated path, which works the same for every curve. pixels that
+// the path skips have no index
+pub struct PathIndex {
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
+    // only built once a pixel is looked up
+    indices: OnceCell<Vec<u32>>,
+}
+
+impl PathIndex {
+    pub fn new(points: &[Point], w: u32, h: u32) -> Self {
+        Self {
+            width: w,
+            height: h,
+            pixels: pixel_indices(points, w),
+            indices: OnceCell::new(),
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.pixels.len() as u32
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn point_at(&self, idx: u32) -> Point {
+        let pixel = self.pixels[idx as usize];
+        Point((pixel % self.width) as i32, (pixel / self.width) as i32)
+    }
+
+    pub fn index_of(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.indices
+            .get_or_init(|| {
+                let mut indices = vec![u32::MAX; (self.width * self.height) as usize];
+                for (idx, &pixel) in self.pixels.iter().enumerate() {
+                    indices[pixel as usize] = idx as u32;
+                }
+                indices
+            })
+            .get((y * self.width + x) as usize)
+            .copied()
+            .filter(|&idx| idx != u32::MAX)
+    }
+
+    // where the pixel shown at (x, y) at `offset` is after `steps` more steps,
+    // without walking the path. it stays inside its velocity segment
+    pub fn position_after(
+        &self,
+        x: u32,
+        y: u32,
+        offset: usize,
+        steps: i64,
+        velocity_segments: &[VelocitySegment],
+    ) -> Option<Point> {
+        let idx = self.index_of(x, y)? as usize;
+        let segment = velocity_segments
+            .iter()
+            .find(|segment| segment.range.contains(&idx))?;
+        Some(self.point_at(segment.index_after(idx, offset, steps, self.pixels.len()) as u32))
+    }
+}
+
+// the index into the pixel data of every point
+pub fn pixel_indices(points: &[Point], w: u32) -> Vec<u32> {
+    points
+        .iter()
+        .map(|&Point(x, y)| y as u32 * w + x as u32)
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug, Default)]
+pub struct PathDiagnostics {
+    pub duplicates: u32,
+    pub missing: u32,
+    pub out_of_range: u32,
+    pub first_duplicate: Option<(i32, i32)>,
+    pub first_missing: Option<(i32, i32)>,
+    pub first_out_of_range: Option<(i32, i32)>,
+}
+
+impl std::fmt::Display for PathDiagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problems = [
+            ("duplicate", self.duplicates, self.first_duplicate),
+            ("missing", self.missing, self.first_missing),
+            ("out of range", self.out_of_range, self.first_out_of_range),
+        ];
+        let mut first = true;
+        for (name, count, example) in problems {
+            let Some((x, y)) = example else {
+                continue;
+            };
+            if !first {
+                write!(f, ", ")?;
+            }
+            first = false;
+            write!(f, "{count} {name} (first at {x}, {y})")?;
+        }
+        Ok(())
+    }
+}
+
+// keeps the points inside the mask in curve order and shrinks the cycles to
+// match, dropping the ones left empty. an empty mask keeps everything
+pub fn masked(
+    points: Vec<Point>,
+    cycles: &[Cycle],
+    mask: &[bool],
+    w: u32,
+) -> (Vec<Point>, Vec<Cycle>) {
+    if mask.is_empty() {
+        return (points, cycles.to_vec());
+    }
+    let in_mask = |&Point(x, y): &Point| mask[(y as u32 * w + x as u32) as usize];
+    let mut masked_cycles = Vec::with_capacity(cycles.len());
+    let mut start = 0;
+    for cycle in cycles {
+        let end = start + cycle.len as usize;
+        let len = points[start..end]
+            .iter()
+            .filter(|point| in_mask(point))
+            .count() as u32;
+        start = end;
+        if len != 0 {
+            masked_cycles.push(Cycle {
+                len,
+                phase: cycle.phase % len,
+                ..*cycle
+            });
+        }
+    }
+    (points.into_iter().filter(in_mask).collect(), masked_cycles)
+}
+
+// a path has to visit every pixel of the image exactly once, otherwise
+// rotating along it would lose or copy pixels
+pub fn validate(points: &[Point], w: u32, h: u32) -> Result<(), PathDiagnostics> {
+    let mut diagnostics = PathDiagnostics::default();
+    let mut visited = vec![false; (w * h) as usize];
+    for &Point(x, y) in points {
+        if !(0..w as i32).contains(&x) || !(0..h as i32).contains(&y) {
+            diagnostics.out_of_range += 1;
+            diagnostics.first_out_of_range.get_or_insert((x, y));
+            continue;
+        }
+        let visited = &mut visited[(y as u32 * w + x as u32) as usize];
+        if *visited {
+            diagnostics.duplicates += 1;
+            diagnostics.first_duplicate.get_or_insert((x, y));
+        }
+        *visited = true;
+    }
+    for (idx, _) in visited.iter().enumerate().filter(|(_, visited)| !**visited) {
+        diagnostics.missing += 1;
+        diagnostics
+            .first_missing
+            .get_or_insert(((idx as u32 % w) as i32, (idx as u32 / w) as i32));
+    }
+    if diagnostics == PathDiagnostics::default() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+// orders every pixel of the image by its index along a curve that may cover
+// more than the image, which skips the indices that fall outside of it
+fn sorted_by_key(w: u32, h: u32, key: impl Fn(u32, u32) -> u64) -> Vec<Point> {
+    let mut points: Vec<_> = (0..h).flat_map(|y| (0..w).map(move |x| (x, y))).collect();
+    points.sort_by_cached_key(|&(x, y)| key(x, y));
+    points
+        .into_iter()
+        .map(|(x, y)| Point(x as i32, y as i32))
+        .collect()
+}
+
+pub fn hilbert_xy2d(mut x: u32, mut y: u32, side: u32) -> u64 {
+    let mut idx = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s != 0) as u32;
+        let ry = (y & s != 0) as u32;
+        idx += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    idx
+}
+
+// four hilbert curves joined into a loop, so the last pixel of the path is
+// next to the first. the loop is only closed when the image is a power of two
+// square, cropping or padding cuts it open again
+pub fn moore_xy2d(x: u32, y: u32, side: u32) -> u64 {
+    let half = side / 2;
+    let (mut x, qx) = (x % half, x / half);
+    let (mut y, qy) = (y % half, y / half);
+    // bottom left, top left, top right then bottom right quadrant
+    let (quadrant, flip_x) = match (qx, qy) {
+        (0, 1) => (0, true),
+        (0, _) => (1, false),
+        (_, 0) => (2, false),
+        _ => (3, true),
+    };
+    if flip_x {
+        x = half - 1 - x;
+    } else {
+        y = half - 1 - y;
+    }
+    quadrant * half as u64 * half as u64 + hilbert_xy2d(x, y, half)
+}
+
+// a closed loop through every pixel for any rectangle, built by walking
+// around a spanning tree of 2x2 blocks that follows the gilbert curve over
+// the blocks
+fn gilbert_loop(w: u32, h: u32) -> Vec<Point> {
+    if w < 2 || h < 2 {
+        return gilbert_points(w, h, Orientation::Auto);
+    }
+    let blocks = gilbert_points(w / 2, h / 2, Orientation::Auto);
+    let tree_edges = blocks.windows(2).filter_map(|pair| {
+        let (Point(ax, ay), Point(bx, by)) = (&pair[0], &pair[1]);
+        // the gilbert curve over the blocks may take a diagonal step
+        ((ax - bx).abs() + (ay - by).abs() == 1)
+            .then_some(((*ax as u32, *ay as u32), (*bx as u32, *by as u32)))
+    });
+    block_tree_loop(w, h, tree_edges)
+}
+
+// a maze like loop through every pixel, walking around a random spanning
+// tree of 2x2 blocks
+fn maze(w: u32, h: u32, seed: u32) -> Vec<Point> {
+    if w < 2 || h < 2 {
+        return gilbert_points(w, h, Orientation::Auto);
+    }
+    let (bw, bh) = (w / 2, h / 2);
+    let mut tree_edges: Vec<_> = (0..bh)
+        .flat_map(|by| (0..bw).map(move |bx| (bx, by)))
+        .flat_map(|(bx, by)| {
+            let right = (bx + 1 < bw).then_some(((bx, by), (bx + 1, by)));
+            let down = (by + 1 < bh).then_some(((bx, by), (bx, by + 1)));
+            right.into_iter().chain(down)
+        })
+        .collect();
+    SplitMix64::new(seed).shuffle(&mut tree_edges);
+    block_tree_loop(w, h, tree_edges)
+}
+
+// the loop around a spanning tree of the 2x2 blocks of the image, which is
+// grown from `tree_edges` between neighbouring blocks in order. an odd last
+// row or column is folded into the block next to it, and when both sides are
+// odd there is no such loop so the corner pixel is reached with one diagonal
+// step
+fn block_tree_loop(
+    w: u32,
+    h: u32,
+    tree_edges: impl IntoIterator<Item = ((u32, u32), (u32, u32))>,
+) -> Vec<Point> {
+    let (bw, bh) = (w / 2, h / 2);
+    let idx = |x: u32, y: u32| (y * w + x) as usize;
+    let corners = |bx: u32, by: u32| {
+        let (x, y) = (bx * 2, by * 2);
+        [idx(x, y), idx(x + 1, y), idx(x + 1, y + 1), idx(x, y + 1)]
+    };
+
+    // every block starts out as its own clockwise loop
+    let mut next = vec![0; (w * h) as usize];
+    for by in 0..bh {
+        for bx in 0..bw {
+            let [tl, tr, br, bl] = corners(bx, by);
+            next[tl] = tr;
+            next[tr] = br;
+            next[br] = bl;
+            next[bl] = tl;
+        }
+    }
+
+    // joining two neighbouring blocks of the tree replaces the sides they
+    // share with two edges across, which merges their loops into one
+    let mut components = DisjointSet::new((bw * bh) as usize);
+    let mut join = |(ax, ay): (u32, u32), (bx, by): (u32, u32)| {
+        let ((ax, ay), (bx, by)) = if (ax, ay) < (bx, by) {
+            ((ax, ay), (bx, by))
+        } else {
+            ((bx, by), (ax, ay))
+        };
+        if !components.union((ay * bw + ax) as usize, (by * bw + bx) as usize) {
+            return;
+        }
+        let [_, a_tr, a_br, a_bl] = corners(ax, ay);
+        let [b_tl, b_tr, _, b_bl] = corners(bx, by);
+        if ay == by {
+            next[a_tr] = b_tl;
+            next[b_bl] = a_br;
+        } else {
+            next[a_br] = b_tr;
+            next[b_tl] = a_bl;
+        }
+    };
+    for (a, b) in tree_edges {
+        join(a, b);
+    }
+    // connect whatever the given edges left apart
+    for by in 0..bh {
+        for bx in 0..bw {
+            if bx + 1 < bw {
+                join((bx, by), (bx + 1, by));
+            }
+            if by + 1 < bh {
+                join((bx, by), (bx, by + 1));
+            }
+        }
+    }
+
+    if w % 2 == 1 {
+        for y in (0..bh * 2).step_by(2) {
+            next[idx(w - 2, y)] = idx(w - 1, y);
+            next[idx(w - 1, y)] = idx(w - 1, y + 1);
+            next[idx(w - 1, y + 1)] = idx(w - 2, y + 1);
+        }
+    }
+    if h % 2 == 1 {
+        for x in (0..bw * 2).step_by(2) {
+            next[idx(x + 1, h - 2)] = idx(x + 1, h - 1);
+            next[idx(x + 1, h - 1)] = idx(x, h - 1);
+            next[idx(x, h - 1)] = idx(x, h - 2);
+        }
+    }
+    if w % 2 == 1 && h % 2 == 1 {
+        next[idx(w - 1, h - 2)] = idx(w - 1, h - 1);
+        next[idx(w - 1, h - 1)] = idx(w - 2, h - 2);
+    }
+
+    let mut pixel = 0;
+    (0..w * h)
+        .map(|_| {
+            let point = Point((pixel as u32 % w) as i32, (pixel as u32 / w) as i32);
+            pixel = next[pixel];
+            point
+        })
+        .collect()
+}
+
+// small seedable generator so a seed gives the same path everywhere
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u32) -> Self {
+        Self { state: seed as u64 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    // returns whether the two were apart before
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a] = b;
+        a != b
+    }
+}
+
+// one level of a 3x3 Peano style recursion, indexed by [x][y] of the cell:
+// the position of the cell along the curve and the symmetry applied to the
+// sub-curve drawn inside of it as (transpose, flip x, flip y)
+pub struct PeanoPattern {
+    order: [[u64; 3]; 3],
+    symmetries: [[(bool, bool, bool); 3]; 3],
+}
+
+// runs up and down the columns, from the top left to the bottom right corner
+pub const PEANO: PeanoPattern = PeanoPattern {
+    order: [[0, 1, 2], [5, 4, 3], [6, 7, 8]],
+    symmetries: [
+        [
+            (false, false, false),
+            (false, true, false),
+            (false, false, false),
+        ],
+        [
+            (false, false, true),
+            (false, true, true),
+            (false, false, true),
+        ],
+        [
+            (false, false, false),
+            (false, true, false),
+            (false, false, false),
+        ],
+    ],
+};
+
+// switches back on itself, from the top left to the top right corner
+pub const PEANO_MEANDER: PeanoPattern = PeanoPattern {
+    order: [[0, 1, 2], [7, 6, 3], [8, 5, 4]],
+    symmetries: [
+        [
+            (true, false, false),
+            (true, false, false),
+            (false, false, false),
+        ],
+        [
+            (true, true, true),
+            (true, true, true),
+            (false, false, false),
+        ],
+        [
+            (false, false, false),
+            (false, true, true),
+            (false, false, false),
+        ],
+    ],
+};
+
+// peano curves only exist for powers of three, other sizes are cut out of
+// the smallest power of three square that covers the image
+fn peano_side(w: u32, h: u32) -> u32 {
+    let mut side = 1;
+    while side < w.max(h) {
+        side *= 3;
+    }
+    side
+}
+
+pub fn peano_xy2d(pattern: &PeanoPattern, mut x: u32, mut y: u32, side: u32) -> u64 {
+    let mut idx = 0;
+    let mut s = side / 3;
+    while s > 0 {
+        let (cx, cy) = ((x / s) as usize, (y / s) as usize);
+        idx = idx * 9 + pattern.order[cx][cy];
+        x %= s;
+        y %= s;
+        let (transpose, flip_x, flip_y) = pattern.symmetries[cx][cy];
+        if flip_x {
+            x = s - 1 - x;
+        }
+        if flip_y {
+            y = s - 1 - y;
+        }
+        if transpose {
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 3;
+    }
+    idx
+}
+
+// interleaves the bits of both coordinates with x in the lowest bit, so a
+// horizontal pair of pixels comes before the pair below it
+pub fn morton_xy2d(x: u32, y: u32) -> u64 {
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64;
+        v = (v | (v << 16)) & 0x0000_ffff_0000_ffff;
+        v = (v | (v << 8)) & 0x00ff_00ff_00ff_00ff;
+        v = (v | (v << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+        v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+        (v | (v << 1)) & 0x5555_5555_5555_5555
+    }
+    spread(x) | (spread(y) << 1)
+}
+
+// row by row when horizontal and column by column otherwise, a serpentine
+// scan runs every other line backwards so consecutive lines join up
+pub fn scan_d2xy(idx: u32, w: u32, h: u32, horizontal: bool, serpentine: bool) -> Point {
+    let line_len = if horizontal { w } else { h };
+    let (line, mut along) = (idx / line_len, idx % line_len);
+    if serpentine && line % 2 == 1 {
+        along = line_len - 1 - along;
+    }
+    if horizontal {
+        Point(along as i32, line as i32)
+    } else {
+        Point(line as i32, along as i32)
+    }
+}
+
+// walks inwards from the top left corner, turning whenever the next pixel is
+// off the image or already visited
+fn spiral(w: u32, h: u32, winding: Winding) -> Vec<Point> {
+    let turns = match winding {
+        Winding::Clockwise => [(1, 0), (0, 1), (-1, 0), (0, -1)],
+        Winding::CounterClockwise => [(0, 1), (1, 0), (0, -1), (-1, 0)],
+    };
+    let mut visited = vec![false; (w * h) as usize];
+    let mut points = Vec::with_capacity(visited.len());
+    let (mut x, mut y, mut turn) = (0, 0, 0);
+    for _ in 0..w * h {
+        visited[(y * w as i32 + x) as usize] = true;
+        points.push(Point(x, y));
+        for _ in 0..turns.len() {
+            let (dx, dy) = turns[turn];
+            let (nx, ny) = (x + dx, y + dy);
+            if (0..w as i32).contains(&nx)
+                && (0..h as i32).contains(&ny)
+                && !visited[(ny * w as i32 + nx) as usize]
+            {
+                (x, y) = (nx, ny);
+                break;
+            }
+            turn = (turn + 1) % turns.len();
+        }
+    }
+    points
+}
+
+// rings of whole pixel distances from the center going outwards, each ring
+// ordered by its angle starting from the right of the center
+fn circular_spiral_key(x: u32, y: u32, w: u32, h: u32, winding: Winding) -> u64 {
+    let dx = x as f64 - (w as f64 - 1.0) / 2.0;
+    let dy = y as f64 - (h as f64 - 1.0) / 2.0;
+    let ring = dx.hypot(dy).round() as u64;
+    // y points down so a growing angle already turns clockwise on screen
+    let angle = match winding {
+        Winding::Clockwise => dy.atan2(dx),
+        Winding::CounterClockwise => (-dy).atan2(dx),
+    }
+    .rem_euclid(std::f64::consts::TAU);
+    let turn = (angle / std::f64::consts::TAU * u32::MAX as f64) as u64;
+    (ring << 32) | turn
+}
+
+// walks the gilbert curve over the flat parts of the image, and whenever it
+// reaches a pixel on an edge follows the edge as far as it goes before going
+// on. edges are where the sobel gradient of the luminance reaches
+// `threshold`, and run across the gradient
+fn edges(w: u32, h: u32, pixels: Pixels, threshold: u32, orientation: Orientation) -> Vec<Point> {
+    let luminance = |x: i32, y: i32| {
+        let x = x.clamp(0, w as i32 - 1) as u32;
+        let y = y.clamp(0, h as i32 - 1) as u32;
+        operators::luminance(pixels.at(x, y)) as i32 / 256
+    };
+    let gradients: Vec<(i32, i32)> = (0..h as i32)
+        .flat_map(|y| (0..w as i32).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let gx = luminance(x + 1, y - 1) + 2 * luminance(x + 1, y) + luminance(x + 1, y + 1)
+                - luminance(x - 1, y - 1)
+                - 2 * luminance(x - 1, y)
+                - luminance(x - 1, y + 1);
+            let gy = luminance(x - 1, y + 1) + 2 * luminance(x, y + 1) + luminance(x + 1, y + 1)
+                - luminance(x - 1, y - 1)
+                - 2 * luminance(x, y - 1)
+                - luminance(x + 1, y - 1);
+            (gx, gy)
+        })
+        .collect();
+    let is_edge = |idx: usize| {
+        let (gx, gy) = gradients[idx];
+        gx.unsigned_abs() + gy.unsigned_abs() >= threshold.max(1)
+    };
+
+    let mut visited = vec![false; (w * h) as usize];
+    let mut points = Vec::with_capacity(visited.len());
+    for Point(x, y) in gilbert_points(w, h, orientation) {
+        let idx = (y as u32 * w + x as u32) as usize;
+        if visited[idx] {
+            continue;
+        }
+        visited[idx] = true;
+        points.push(Point(x, y));
+        if !is_edge(idx) {
+            continue;
+        }
+        let (mut x, mut y, mut idx) = (x, y, idx);
+        let mut previous_step = (0.0, 0.0);
+        loop {
+            let (gx, gy) = gradients[idx];
+            // keep going the way the edge was followed so far
+            let mut tangent = (-gy as f64, gx as f64);
+            if tangent.0 * previous_step.0 + tangent.1 * previous_step.1 < 0.0 {
+                tangent = (-tangent.0, -tangent.1);
+            }
+            let next = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .filter(|&step| step != (0, 0))
+                .filter_map(|(dx, dy)| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= w as i32 || ny >= h as i32 {
+                        return None;
+                    }
+                    let next_idx = (ny as u32 * w + nx as u32) as usize;
+                    if visited[next_idx] || !is_edge(next_idx) {
+                        return None;
+                    }
+                    let along = (dx as f64 * tangent.0 + dy as f64 * tangent.1)
+                        / (dx as f64).hypot(dy as f64);
+                    Some((along, (dx, dy), next_idx))
+                })
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+            let Some((_, step, next_idx)) = next else {
+                break;
+            };
+            (x, y, idx) = (x + step.0, y + step.1, next_idx);
+            previous_step = (step.0 as f64, step.1 as f64);
+            visited[idx] = true;
+            points.push(Point(x, y));
+        }
+    }
+    points
+}
+
+// rings of `ring_width` around the middle, each ordered clockwise
+fn rings_key(x: u32, y: u32, w: u32, h: u32, ring_width: u32) -> u64 {
+    let dx = x as f64 - (w as f64 - 1.0) / 2.0;
+    let dy = y as f64 - (h as f64 - 1.0) / 2.0;
+    let ring = (dx.hypot(dy) / ring_width as f64) as u64;
+    let angle = dy.atan2(dx).rem_euclid(std::f64::consts::TAU);
+    let turn = (angle / std::f64::consts::TAU * u32::MAX as f64) as u64;
+    (ring << 32) | turn
+}
+
+// every column is shifted down by its x, wrapping around the bottom edge
 pub fn shift(idx: u32, w: u32, h: u32) -> Point {
     let x = idx % w;
-    let y = idx / w + x;
+    let y = (idx / w + x) % h;
     Point(x as i32, y as i32)
 }
 
 // SPDX-License-Identifier: BSD-2-Clause
 // Copyright (c) 2024 abetusk
 
-pub fn gilbert_d2xy(idx: u32, w: u32, h: u32) -> Point {
-    if w >= h {
-        gilbert_d2xy_r(
-            idx as i32,
-            0,
-            Point(0, 0),
-            Point(w as i32, 0),
-            Point(0, h as i32),
-        )
+// walks the whole curve once and keeps the splits that are still to be walked
+// on an explicit stack
+pub fn gilbert_points(w: u32, h: u32, orientation: Orientation) -> Vec<Point> {
+    let mut points = Vec::with_capacity((w * h) as usize);
+    if w == 0 || h == 0 {
+        return points;
+    }
+    let mut stack = if orientation.is_horizontal(w, h) {
+        vec![(Point(0, 0), Point(w as i32, 0), Point(0, h as i32))]
     } else {
-        gilbert_d2xy_r(
-            idx as i32,
-            0,
-            Point(0, 0),
-            Point(0, h as i32),
-            Point(w as i32, 0),
-        )
+        vec![(Point(0, 0), Point(0, h as i32), Point(w as i32, 0))]
+    };
+    while let Some((p, a, b)) = stack.pop() {
+        let w = (a.0 + a.1).abs();
+        let h = (b.0 + b.1).abs();
+
+        let da = Point(a.0.signum(), a.1.signum());
+        let db = Point(b.0.signum(), b.1.signum());
+
+        if h == 1 {
+            points.extend((0..w).map(|i| Point(p.0 + da.0 * i, p.1 + da.1 * i)));
+            continue;
+        }
+        if w == 1 {
+            points.extend((0..h).map(|i| Point(p.0 + db.0 * i, p.1 + db.1 * i)));
+            continue;
+        }
+
+        let mut a2 = Point(a.0 / 2, a.1 / 2);
+        let mut b2 = Point(b.0 / 2, b.1 / 2);
+
+        let w2 = (a2.0 + a2.1).abs();
+        let h2 = (b2.0 + b2.1).abs();
+
+        // the sub-curves are pushed last to first so they pop in order
+        if 2 * w > 3 * h {
+            // prefer even steps
+            if w2 % 2 != 0 && w > 2 {
+                a2.0 += da.0;
+                a2.1 += da.1;
+            }
+
+            stack.push((
+                Point(p.0 + a2.0, p.1 + a2.1),
+                Point(a.0 - a2.0, a.1 - a2.1),
+                Point(b.0, b.1),
+            ));
+            stack.push((p, a2, b));
+            continue;
+        }
+
+        // prefer even steps
+        if h2 % 2 != 0 && h > 2 {
+            b2.0 += db.0;
+            b2.1 += db.1;
+        }
+
+        // standard case: one step up, one long horizontal, one step down
+        stack.push((
+            Point(
+                p.0 + (a.0 - da.0) + (b2.0 - db.0),
+                p.1 + (a.1 - da.1) + (b2.1 - db.1),
+            ),
+            Point(-b2.0, -b2.1),
+            Point(-(a.0 - a2.0), -(a.1 - a2.1)),
+        ));
+        stack.push((
+            Point(p.0 + b2.0, p.1 + b2.1),
+            Point(a.0, a.1),
+            Point(b.0 - b2.0, b.1 - b2.1),
+        ));
+        stack.push((p, b2, a2));
     }
+    points
 }
 
-fn gilbert_d2xy_r(dst_idx: i32, cur_idx: i32, p: Point, a: Point, b: Point) -> Point {
-    let mut _p: Point;
-    let mut _a: Point;
-    let mut _b: Point;
-    let mut nxt_idx: i32;
+// the index of a pixel along the curve gilbert_points walks, descending only
+// into the split that contains the pixel
+pub fn gilbert_xy2d(x: u32, y: u32, w: u32, h: u32, orientation: Orientation) -> u32 {
+    let dst = Point(x as i32, y as i32);
+    let (mut p, mut a, mut b) = if orientation.is_horizontal(w, h) {
+        (Point(0, 0), Point(w as i32, 0), Point(0, h as i32))
+    } else {
+        (Point(0, 0), Point(0, h as i32), Point(w as i32, 0))
+    };
+    let mut cur_idx = 0;
+    loop {
+        let w = (a.0 + a.1).abs();
+        let h = (b.0 + b.1).abs();
+
+        let da = Point(a.0.signum(), a.1.signum());
+        let db = Point(b.0.signum(), b.1.signum());
+
+        if h == 1 {
+            return (cur_idx + (dst.0 - p.0) * da.0 + (dst.1 - p.1) * da.1) as u32;
+        }
+        if w == 1 {
+            return (cur_idx + (dst.0 - p.0) * db.0 + (dst.1 - p.1) * db.1) as u32;
+        }
+
+        let mut a2 = Point(a.0 / 2, a.1 / 2);
+        let mut b2 = Point(b.0 / 2, b.1 / 2);
+
+        let w2 = (a2.0 + a2.1).abs();
+        let h2 = (b2.0 + b2.1).abs();
+
+        if 2 * w > 3 * h {
+            // prefer even steps
+            if w2 % 2 != 0 && w > 2 {
+                a2.0 += da.0;
+                a2.1 += da.1;
+            }
+
+            if contains(&dst, &p, &a2, &b) {
+                a = a2;
+                continue;
+            }
+            cur_idx += ((a2.0 + a2.1) * (b.0 + b.1)).abs();
 
-    let w = (a.0 + a.1).abs();
-    let h = (b.0 + b.1).abs();
+            p = Point(p.0 + a2.0, p.1 + a2.1);
+            a = Point(a.0 - a2.0, a.1 - a2.1);
+            continue;
+        }
+
+        // prefer even steps
+        if h2 % 2 != 0 && h > 2 {
+            b2.0 += db.0;
+            b2.1 += db.1;
+        }
+
+        // standard case: one step up, one long horizontal, one step down
+        if contains(&dst, &p, &b2, &a2) {
+            (a, b) = (b2, a2);
+            continue;
+        }
+        cur_idx += ((b2.0 + b2.1) * (a2.0 + a2.1)).abs();
 
-    let da = Point(a.0.signum(), a.1.signum());
-    let db = Point(b.0.signum(), b.1.signum());
-    let i = dst_idx - cur_idx;
+        let _p = Point(p.0 + b2.0, p.1 + b2.1);
+        let _b = Point(b.0 - b2.0, b.1 - b2.1);
+        if contains(&dst, &_p, &a, &_b) {
+            (p, b) = (_p, _b);
+            continue;
+        }
+        cur_idx += ((a.0 + a.1) * (_b.0 + _b.1)).abs();
 
-    if h == 1 {
-        return Point(p.0 + da.0 * i, p.1 + da.1 * i);
+        p = Point(
+            p.0 + (a.0 - da.0) + (b2.0 - db.0),
+            p.1 + (a.1 - da.1) + (b2.1 - db.1),
+        );
+        (a, b) = (Point(-b2.0, -b2.1), Point(-(a.0 - a2.0), -(a.1 - a2.1)));
     }
-    if w == 1 {
-        return Point(p.0 + db.0 * i, p.1 + db.1 * i);
+}
+
+// whether the rectangle spanned by `a` and `b` from `p` contains `dst`
+fn contains(dst: &Point, p: &Point, a: &Point, b: &Point) -> bool {
+    let within = |dst: i32, p: i32, d: i32| {
+        if d < 0 {
+            p + d < dst && dst <= p
+        } else {
+            p <= dst && dst < p + d
+        }
+    };
+    within(dst.0, p.0, a.0 + b.0) && within(dst.1, p.1, a.1 + b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // no two neighbouring pixels share a colour, so curves that read the
+    // pixels have something to work with
+    fn pixel_data(w: u32, h: u32) -> Vec<u8> {
+        (0..w * h * 4)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect()
     }
 
-    let mut a2 = Point(a.0 / 2, a.1 / 2);
-    let mut b2 = Point(b.0 / 2, b.1 / 2);
+    fn params() -> [CurveParams; 2] {
+        [
+            CurveParams {
+                ring_width: 3,
+                edge_threshold: 40,
+                ..Default::default()
+            },
+            CurveParams {
+                orientation: Orientation::Vertical,
+                fit: Fit::Pad,
+                spiral_direction: SpiralDirection::Outward,
+                winding: Winding::CounterClockwise,
+                seed: 7,
+                ring_width: 1,
+                sort_key: SortKey::Hue,
+                edge_threshold: 1,
+            },
+        ]
+    }
 
-    let w2 = (a2.0 + a2.1).abs();
-    let h2 = (b2.0 + b2.1).abs();
+    fn assert_bijection(points: &[Point], cycles: &[Cycle], w: u32, h: u32, what: &str) {
+        assert!(validate(points, w, h).is_ok(), "{what} at {w}x{h}");
+        assert_eq!(
+            cycles.iter().map(|cycle| cycle.len).sum::<u32>(),
+            w * h,
+            "cycles of {what} at {w}x{h}"
+        );
+    }
 
-    if 2 * w > 3 * h {
-        // prefer even steps
-        if w2 % 2 != 0 && w > 2 {
-            a2.0 += da.0;
-            a2.1 += da.1;
+    // every size up to 13x13, with the default options and the other ones
+    fn assert_visits_every_pixel_once(name: &str) {
+        for params in params() {
+            let curve = Curve::from_name(name, params).unwrap();
+            for w in 1..=13 {
+                for h in 1..=13 {
+                    let pixel_data = pixel_data(w, h);
+                    let points = curve.points(w, h, Pixels::new(&pixel_data, w));
+                    let cycles = curve.cycles(&points, w, h);
+                    assert_bijection(&points, &cycles, w, h, name);
+                }
+            }
         }
+    }
+
+    #[test]
+    fn hilbert_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("hilbert");
+    }
 
-        nxt_idx = cur_idx + ((a2.0 + a2.1) * (b.0 + b.1)).abs();
-        if cur_idx <= dst_idx && dst_idx < nxt_idx {
-            return gilbert_d2xy_r(dst_idx, cur_idx, p, a2, b);
+    #[test]
+    fn peano_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("peano");
+    }
+
+    #[test]
+    fn peano_meander_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("peano-meander");
+    }
+
+    #[test]
+    fn moore_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("moore");
+    }
+
+    #[test]
+    fn gilbert_loop_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("gilbert-loop");
+    }
+
+    #[test]
+    fn z_order_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("z-order");
+    }
+
+    #[test]
+    fn raster_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("raster");
+    }
+
+    #[test]
+    fn serpentine_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("serpentine");
+    }
+
+    #[test]
+    fn spiral_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("spiral");
+    }
+
+    #[test]
+    fn circular_spiral_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("circular-spiral");
+    }
+
+    // the signed angle the path sweeps around the center, clockwise on screen
+    // is positive since y points down
+    fn swept_angle(points: &[Point], w: u32, h: u32) -> f64 {
+        let angles: Vec<f64> = points
+            .iter()
+            .filter_map(|&Point(x, y)| {
+                let dx = x as f64 - (w as f64 - 1.0) / 2.0;
+                let dy = y as f64 - (h as f64 - 1.0) / 2.0;
+                (dx != 0.0 || dy != 0.0).then(|| dy.atan2(dx))
+            })
+            .collect();
+        angles
+            .windows(2)
+            .map(|pair| {
+                (pair[1] - pair[0] + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU)
+                    - std::f64::consts::PI
+            })
+            .sum()
+    }
+
+    #[test]
+    fn spirals_keep_their_winding_in_both_directions() {
+        for name in ["spiral", "circular-spiral"] {
+            for spiral_direction in [SpiralDirection::Inward, SpiralDirection::Outward] {
+                for (winding, sign) in
+                    [(Winding::Clockwise, 1.0), (Winding::CounterClockwise, -1.0)]
+                {
+                    let curve = Curve::from_name(
+                        name,
+                        CurveParams {
+                            spiral_direction,
+                            winding,
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                    for (w, h) in [(9, 9), (12, 7), (7, 12)] {
+                        let points = curve.points(w, h, Pixels::new(&[], w));
+                        assert!(
+                            swept_angle(&points, w, h) * sign > 0.0,
+                            "{name} {spiral_direction:?} {winding:?} at {w}x{h}"
+                        );
+                    }
+                }
+            }
         }
-        let cur_idx = nxt_idx;
+    }
+
+    #[test]
+    fn shuffle_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("shuffle");
+    }
+
+    #[test]
+    fn maze_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("maze");
+    }
+
+    #[test]
+    fn shift_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("shift");
+    }
+
+    #[test]
+    fn validate_reports_what_is_wrong_with_a_path() {
+        let points = [
+            Point(0, 0),
+            Point(1, 0),
+            Point(1, 0),
+            Point(0, 1),
+            Point(5, 1),
+            Point(2, -1),
+            Point(1, 1),
+        ];
+        let diagnostics = validate(&points, 3, 2).unwrap_err();
+        assert_eq!(
+            diagnostics,
+            PathDiagnostics {
+                duplicates: 1,
+                missing: 2,
+                out_of_range: 2,
+                first_duplicate: Some((1, 0)),
+                first_missing: Some((2, 0)),
+                first_out_of_range: Some((5, 1)),
+            }
+        );
+        assert_eq!(
+            diagnostics.to_string(),
+            "1 duplicate (first at 1, 0), 2 missing (first at 2, 0), 2 out of range (first at 5, 1)"
+        );
 
-        _p = Point(p.0 + a2.0, p.1 + a2.1);
-        _a = Point(a.0 - a2.0, a.1 - a2.1);
+        let diagnostics = validate(&[], 2, 2).unwrap_err();
+        assert_eq!((diagnostics.duplicates, diagnostics.missing), (0, 4));
+        assert_eq!(diagnostics.to_string(), "4 missing (first at 0, 0)");
+    }
 
-        return gilbert_d2xy_r(dst_idx, cur_idx, _p, _a, b);
+    #[test]
+    fn gilbert_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("gilbert");
     }
 
-    // prefer even steps
-    if h2 % 2 != 0 && h > 2 {
-        b2.0 += db.0;
-        b2.1 += db.1;
+    #[test]
+    fn gilbert_xy2d_inverts_gilbert_points() {
+        for orientation in [
+            Orientation::Auto,
+            Orientation::Horizontal,
+            Orientation::Vertical,
+        ] {
+            for w in 1..=13 {
+                for h in 1..=13 {
+                    for (idx, &Point(x, y)) in gilbert_points(w, h, orientation).iter().enumerate()
+                    {
+                        assert_eq!(
+                            gilbert_xy2d(x as u32, y as u32, w, h, orientation),
+                            idx as u32,
+                            "{orientation:?} at {w}x{h}"
+                        );
+                    }
+                }
+            }
+        }
     }
 
-    // standard case: one step up, one long horizontal, one step down
-    nxt_idx = cur_idx + ((b2.0 + b2.1) * (a2.0 + a2.1)).abs();
-    if cur_idx <= dst_idx && dst_idx < nxt_idx {
-        return gilbert_d2xy_r(dst_idx, cur_idx, p, b2, a2);
+    #[test]
+    fn position_after_follows_the_pixel_shown() {
+        let (w, h) = (7, 5);
+        let curve = Curve::from_name("serpentine", CurveParams::default()).unwrap();
+        let points = curve.points(w, h, Pixels::new(&[], w));
+        let cycles = curve.cycles(&points, w, h);
+        let path_index = PathIndex::new(&points, w, h);
+        let path_len = path_index.len() as usize;
+        let segments =
+            crate::velocity::VelocityProfile::Ramp.segments(path_index.pixels(), &cycles, 4, &[]);
+        // the index along the path of the original pixel shown at `idx`
+        let source = |idx: usize, offset: usize| {
+            let segment = segments
+                .iter()
+                .find(|segment| segment.range.contains(&idx))
+                .unwrap();
+            segment.range.start
+                + (idx - segment.range.start + segment.offset(offset, path_len))
+                    % segment.range.len()
+        };
+        for offset in [0, 3, 20] {
+            for steps in [-9, -1, 1, 2, 17, 70] {
+                let next_offset = (offset as i64 + steps).rem_euclid(path_len as i64) as usize;
+                for (idx, &Point(x, y)) in points.iter().enumerate() {
+                    let Point(next_x, next_y) = path_index
+                        .position_after(x as u32, y as u32, offset, steps, &segments)
+                        .unwrap();
+                    let next_idx = path_index.index_of(next_x as u32, next_y as u32).unwrap();
+                    assert_eq!(
+                        source(next_idx as usize, next_offset),
+                        source(idx, offset),
+                        "{steps} steps from offset {offset}"
+                    );
+                }
+            }
+        }
     }
-    let cur_idx = nxt_idx;
 
-    nxt_idx = cur_idx + ((a.0 + a.1) * (b.0 - b2.0 + (b.1 - b2.1))).abs();
-    if cur_idx <= dst_idx && dst_idx < nxt_idx {
-        _p = Point(p.0 + b2.0, p.1 + b2.1);
-        _b = Point(b.0 - b2.0, b.1 - b2.1);
-        return gilbert_d2xy_r(dst_idx, cur_idx, _p, a, _b);
+    #[test]
+    fn rings_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("rings");
     }
-    let cur_idx = nxt_idx;
 
-    _p = Point(
-        p.0 + (a.0 - da.0) + (b2.0 - db.0),
-        p.1 + (a.1 - da.1) + (b2.1 - db.1),
-    );
-    _a = Point(-b2.0, -b2.1);
-    _b = Point(-(a.0 - a2.0), -(a.1 - a2.1));
+    #[test]
+    fn every_tiling_visits_every_pixel_once() {
+        for (columns, rows) in [(1, 1), (2, 3), (3, 2), (5, 5)] {
+            for mode in [TileMode::Together, TileMode::Phase, TileMode::Speed] {
+                let tiling = Tiling {
+                    columns,
+                    rows,
+                    mode,
+                    seed: 3,
+                };
+                for (name, _) in Curve::NAMES {
+                    let curve = Curve::from_name(name, params()[0]).unwrap();
+                    for w in 1..=13 {
+                        for h in 1..=13 {
+                            let pixel_data = pixel_data(w, h);
+                            let points = tiling.points(&curve, w, h, Pixels::new(&pixel_data, w));
+                            let cycles = tiling.cycles(&curve, &points, w, h);
+                            assert_bijection(&points, &cycles, w, h, name);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("sorted");
+    }
 
-    gilbert_d2xy_r(dst_idx, cur_idx, _p, _a, _b)
+    #[test]
+    fn edges_visits_every_pixel_once() {
+        assert_visits_every_pixel_once("edges");
+    }
 }
@@ -0,0 +1,114 @@
+use std::sync::{
+    atomic::{AtomicI32, AtomicU32, Ordering},
+    Mutex,
+};
+use wasm_bindgen::prelude::*;
+
+// pool workers wait on GENERATION for a new job and the worker that hands out
+// a job waits on PENDING for all of them to finish their chunk
+pub static POOL_SIZE: AtomicU32 = AtomicU32::new(0);
+static GENERATION: AtomicI32 = AtomicI32::new(0);
+static PENDING: AtomicI32 = AtomicI32::new(0);
+static CURRENT_JOB: Mutex<CurrentJob> = Mutex::new(CurrentJob {
+    job: None,
+    chunks: 0,
+    generation: 0,
+});
+
+type Job<'a> = dyn Fn(usize, usize) + Sync + 'a;
+
+struct CurrentJob {
+    job: Option<&'static Job<'static>>,
+    chunks: usize,
+    generation: i32,
+}
+
+#[wasm_bindgen(js_name = runPoolWorker)]
+pub fn run_pool_worker() {
+    // read before registering so the first job counting this worker is not
+    // missed
+    let mut generation = CURRENT_JOB.lock().unwrap().generation;
+    let chunk = POOL_SIZE.fetch_add(1, Ordering::SeqCst) as usize + 1;
+    loop {
+        while GENERATION.load(Ordering::SeqCst) == generation {
+            wait(&GENERATION, generation);
+        }
+        let (job, chunks) = {
+            let current_job = CURRENT_JOB.lock().unwrap();
+            generation = current_job.generation;
+            // workers that registered after the job was handed out sit it
+            // out, the job may already be gone for them
+            let job = current_job.job.filter(|_| chunk < current_job.chunks);
+            (job, current_job.chunks)
+        };
+        if let Some(job) = job {
+            job(chunk, chunks);
+            if PENDING.fetch_sub(1, Ordering::SeqCst) == 1 {
+                notify(&PENDING);
+            }
+        }
+    }
+}
+
+// runs `job(chunk, chunks)` for every chunk spread over the pool and the
+// calling worker, returning once all of them are done. must not be called on
+// the main thread, which is not allowed to block
+pub fn run(job: &Job<'_>) {
+    let chunks = POOL_SIZE.load(Ordering::SeqCst) as usize + 1;
+    if chunks == 1 {
+        job(0, 1);
+        return;
+    }
+    PENDING.store(chunks as i32 - 1, Ordering::SeqCst);
+    let generation = {
+        let mut current_job = CURRENT_JOB.lock().unwrap();
+        current_job.generation += 1;
+        // the job only has to live until every chunk is done, which is
+        // waited for below
+        current_job.job =
+            Some(unsafe { std::mem::transmute::<&Job<'_>, &'static Job<'static>>(job) });
+        current_job.chunks = chunks;
+        current_job.generation
+    };
+    GENERATION.store(generation, Ordering::SeqCst);
+    notify(&GENERATION);
+
+    job(0, chunks);
+    loop {
+        let pending = PENDING.load(Ordering::SeqCst);
+        if pending == 0 {
+            break;
+        }
+        wait(&PENDING, pending);
+    }
+    CURRENT_JOB.lock().unwrap().job = None;
+}
+
+// the range of `len` items that belongs to `chunk`
+pub fn chunk_range(len: usize, chunk: usize, chunks: usize) -> std::ops::Range<usize> {
+    len * chunk / chunks..len * (chunk + 1) / chunks
+}
+
+#[cfg(target_arch = "wasm32")]
+fn wait(atomic: &AtomicI32, expected: i32) {
+    unsafe {
+        core::arch::wasm32::memory_atomic_wait32(atomic.as_ptr(), expected, -1);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn notify(atomic: &AtomicI32) {
+    unsafe {
+        core::arch::wasm32::memory_atomic_notify(atomic.as_ptr(), u32::MAX);
+    }
+}
+
+// there are no pool workers outside the browser so `run` runs every job
+// inline, this only lets the tests build on the host
+#[cfg(not(target_arch = "wasm32"))]
+fn wait(_atomic: &AtomicI32, _expected: i32) {
+    std::thread::yield_now();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn notify(_atomic: &AtomicI32) {}
@@ -1,5 +1,6 @@
-use crate::{handlers, paths, utils, worker, GlobalState};
+use crate::{handlers, operators, paths, utils, worker, GlobalState};
 use js_sys::{Uint8ClampedArray, WebAssembly};
+use num::Integer;
 use std::sync::atomic::Ordering;
 use wasm_bindgen::prelude::*;
 
@@ -9,6 +10,12 @@ pub struct ImageDimensions {
     height: u32,
 }
 
+impl ImageDimensions {
+    pub fn is_loaded(&self) -> bool {
+        self.width != 0 && self.height != 0
+    }
+}
+
 pub struct Point(pub i32, pub i32);
 
 pub async fn load_image(global_state: &GlobalState) {
@@ -20,25 +27,134 @@ pub async fn load_image(global_state: &GlobalState) {
         .unwrap()
         .data()
         .0;
-    *worker::PIXEL_DATA.lock().unwrap() = pixel_data;
+    *worker::ORIGINAL_PIXEL_DATA.lock().unwrap() = pixel_data.clone();
+    *utils::lock_on_main_thread(&worker::PIXEL_DATA) = pixel_data;
 
     *global_state.image_dimensions.borrow_mut() = ImageDimensions { width, height };
+    load_path(global_state).await;
+}
+
+pub async fn load_path(global_state: &GlobalState) {
+    let ImageDimensions { width, height } = *global_state.image_dimensions.borrow();
+    *worker::MASK.lock().unwrap() = selected_mask(global_state);
+    let params = selected_curve_params(global_state);
+    let curve = paths::Curve::from_name(&global_state.curve_select.value(), params).unwrap();
+    // channels left on the same curve have no curve of their own
+    let channel_curves = global_state
+        .channel_curve_selects
+        .each_ref()
+        .map(|channel_curve_select| paths::Curve::from_name(&channel_curve_select.value(), params));
+    let tiling = paths::Tiling {
+        columns: global_state.tile_columns_input.value_as_number() as u32,
+        rows: global_state.tile_rows_input.value_as_number() as u32,
+        mode: paths::TileMode::from_name(&global_state.tile_mode_select.value()).unwrap(),
+        seed: params.seed,
+    };
     let received_worker_message = utils::worker_operation(
         &global_state.worker,
         worker::WorkerMessage::LoadPath(worker::LoadPathMessage::new(
             width,
             height,
-            paths::gilbert_d2xy,
+            curve,
+            channel_curves,
+            tiling,
         )),
     )
     .await;
-    let handlers::MainMessage::LoadedPath { path_len } = received_worker_message else {
-        panic!(
-            "Expected MainMessage::LoadedPath, got {:?}",
+    match received_worker_message {
+        handlers::MainMessage::LoadedPath { path_len: 0, .. } => {
+            global_state.path_len.set(None);
+            global_state
+                .path_status
+                .set_text_content(Some("The mask does not select any pixel"));
+        }
+        handlers::MainMessage::LoadedPath {
+            path_len,
+            cycle_count,
+        } => {
+            let masked = !worker::MASK.lock().unwrap().is_empty();
+            global_state.path_len.set(Some(path_len));
+            global_state.cycle_count.set(cycle_count);
+            global_state
+                .timeline_input
+                .set_max(&path_len.saturating_sub(1).to_string());
+            global_state
+                .path_status
+                .set_text_content(Some(&match (masked, cycle_count) {
+                    (false, 1) => format!("Path visits all {path_len} pixels"),
+                    (false, _) => {
+                        format!("Path visits all {path_len} pixels in {cycle_count} cycles")
+                    }
+                    (true, 1) => format!("Path visits the {path_len} pixels in the mask"),
+                    (true, _) => format!(
+                        "Path visits the {path_len} pixels in the mask in {cycle_count} cycles"
+                    ),
+                }));
+        }
+        handlers::MainMessage::InvalidPath(diagnostics) => {
+            global_state.path_len.set(None);
+            global_state
+                .path_status
+                .set_text_content(Some(&format!("Invalid path: {diagnostics}")));
+        }
+        _ => panic!(
+            "Expected MainMessage::LoadedPath or MainMessage::InvalidPath, got {:?}",
             received_worker_message
-        );
-    };
-    global_state.path_len.set(Some(path_len));
+        ),
+    }
+    render_pixel_data(global_state);
+}
+
+// a painted or loaded mask takes in the bright opaque pixels, the alpha mask
+// the opaque pixels of the image
+fn selected_mask(global_state: &GlobalState) -> Vec<bool> {
+    match global_state.mask_source_select.value().as_str() {
+        "canvas" => {
+            let mask_canvas = global_state.mask_ctx.canvas().unwrap();
+            global_state
+                .mask_ctx
+                .get_image_data(
+                    0.0,
+                    0.0,
+                    mask_canvas.width() as f64,
+                    mask_canvas.height() as f64,
+                )
+                .unwrap()
+                .data()
+                .0
+                .chunks_exact(4)
+                .map(|rgba| {
+                    let pixel = u32::from_le_bytes(rgba.try_into().unwrap());
+                    rgba[3] >= 128
+                        && operators::luminance(pixel) >= operators::luminance(u32::MAX) / 2
+                })
+                .collect()
+        }
+        "alpha" => worker::ORIGINAL_PIXEL_DATA
+            .lock()
+            .unwrap()
+            .chunks_exact(4)
+            .map(|rgba| rgba[3] >= 128)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn selected_curve_params(global_state: &GlobalState) -> paths::CurveParams {
+    paths::CurveParams {
+        orientation: paths::Orientation::from_name(&global_state.orientation_select.value())
+            .unwrap(),
+        fit: paths::Fit::from_name(&global_state.fit_select.value()).unwrap(),
+        spiral_direction: paths::SpiralDirection::from_name(
+            &global_state.spiral_direction_select.value(),
+        )
+        .unwrap(),
+        winding: paths::Winding::from_name(&global_state.winding_select.value()).unwrap(),
+        seed: global_state.seed_input.value_as_number() as u32,
+        ring_width: global_state.ring_width_input.value_as_number() as u32,
+        sort_key: paths::SortKey::from_name(&global_state.sort_key_select.value()).unwrap(),
+        edge_threshold: global_state.edge_threshold_input.value_as_number() as u32,
+    }
 }
 
 #[wasm_bindgen]
@@ -51,7 +167,7 @@ extern "C" {
 }
 
 pub fn render_pixel_data(global_state: &GlobalState) {
-    let pixel_data = worker::PIXEL_DATA.lock().unwrap();
+    let pixel_data = utils::lock_on_main_thread(&worker::PIXEL_DATA);
     let pixel_data_base = pixel_data.as_ptr() as usize;
     let pixel_data_len = pixel_data.len() as u32;
     let sliced_pixel_data = Uint8ClampedArray::new(
@@ -78,17 +194,47 @@ pub fn render_pixel_data(global_state: &GlobalState) {
         .ctx
         .put_image_data(image_data, 0.0, 0.0)
         .unwrap();
+    global_state
+        .timeline_input
+        .set_value_as_number(worker::OFFSET.load(Ordering::Relaxed) as f64);
+    let restore_count = worker::RESTORE_COUNT.load(Ordering::Relaxed);
+    let restore_status = match restore_count {
+        0 => String::new(),
+        1 => "Restored to the original once".to_string(),
+        _ => format!("Restored to the original {restore_count} times"),
+    };
+    global_state
+        .restore_status
+        .set_text_content(Some(&restore_status));
 }
 
 pub async fn stop(global_state: &GlobalState) {
     worker::STOP_WORKER_LOOP.store(true, Ordering::Relaxed);
     let received_worker_message = utils::wait_for_worker_message(&global_state.worker).await;
-    if received_worker_message != handlers::MainMessage::Stopped {
+    // the worker may have stopped by itself at the same time
+    if received_worker_message != handlers::MainMessage::Stopped
+        && received_worker_message != handlers::MainMessage::Restored
+    {
         panic!(
-            "Expected MainMessage::Stopped, got {:?}",
+            "Expected MainMessage::Stopped or MainMessage::Restored, got {:?}",
             received_worker_message
         );
     };
+    // a worker that restored by itself never saw the flag, it would stop the
+    // next start right away
+    worker::STOP_WORKER_LOOP.store(false, Ordering::Relaxed);
+    render_pixel_data(global_state);
+}
+
+pub async fn seek(offset: u32, global_state: &GlobalState) {
+    let received_worker_message =
+        utils::worker_operation(&global_state.worker, worker::WorkerMessage::Seek { offset }).await;
+    if received_worker_message != handlers::MainMessage::Sought {
+        panic!(
+            "Expected MainMessage::Sought, got {:?}",
+            received_worker_message
+        );
+    }
     render_pixel_data(global_state);
 }
 
@@ -101,14 +247,42 @@ pub fn change_speed(new_speed_percentage: u32) {
 
 pub fn change_step(new_step_percentage: u32, global_state: &GlobalState) {
     let scaled_step_percentage = ((new_step_percentage as i32 - 50) * 2) as f64;
-    if scaled_step_percentage == 0.0 {
-        worker::STEPS.store(0, Ordering::Relaxed);
-        return;
-    }
-    let path_len = global_state.path_len.get().unwrap() as f64;
-    let log_proportion =
-        path_len.log2() - scaled_step_percentage.abs() * (path_len.log2() - 1.0) / 100.0;
-    let steps =
-        (path_len / 2.0_f64.powf(log_proportion)) as i32 * scaled_step_percentage.signum() as i32;
+    let path_len = global_state.path_len.get().unwrap();
+    let steps = if scaled_step_percentage == 0.0 {
+        0
+    } else {
+        let path_len = path_len as f64;
+        let log_proportion =
+            path_len.log2() - scaled_step_percentage.abs() * (path_len.log2() - 1.0) / 100.0;
+        (path_len / 2.0_f64.powf(log_proportion)) as i32 * scaled_step_percentage.signum() as i32
+    };
     worker::STEPS.store(steps, Ordering::Relaxed);
+    // rearranging operators are only known to be back once they get there
+    // cycles and segments moving at their own speed may all line up before
+    // that, and tiles out of phase may never do
+    let period = if operators::Operator::load() != operators::Operator::Rotate
+        || global_state.tile_mode_select.value() == "phase"
+    {
+        "unknown".to_string()
+    } else if global_state.velocity_select.value() != "constant"
+        || global_state.cycle_count.get() > 1
+    {
+        format!("at most {}", period(path_len, steps))
+    } else {
+        period(path_len, steps).to_string()
+    };
+    global_state.period.set_text_content(Some(&period));
+}
+
+// rotating a cycle of `path_len` pixels by `steps` at a time comes back to
+// the original image after this many steps, once for every moved channel
+fn period(path_len: u32, steps: i32) -> u32 {
+    let moved_channels = worker::AlphaMode::load().moved_channels();
+    worker::CHANNEL_STEP_MULTIPLIERS[..moved_channels]
+        .iter()
+        .fold(1, |period, multiplier| {
+            let channel_steps = (steps as i64 * multiplier.load(Ordering::Relaxed) as i64)
+                .rem_euclid(path_len as i64) as u32;
+            period.lcm(&(path_len / path_len.gcd(&channel_steps)))
+        })
 }
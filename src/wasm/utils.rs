@@ -1,9 +1,12 @@
 use crate::{handlers::MainMessage, worker::WorkerMessage};
 use js_sys::{Function, JsString, Promise};
 use num::{Integer, Num, NumCast};
-use std::rc::Rc;
+use std::{
+    rc::Rc,
+    sync::{Mutex, MutexGuard, TryLockError},
+};
 use wasm_bindgen::prelude::*;
-use web_sys::{Document, File, FileReader, MessageEvent, Worker};
+use web_sys::{Document, File, FileReader, HtmlImageElement, MessageEvent, Worker};
 
 pub fn lerp<T: Integer + NumCast + Copy, const N: usize, R: Num + NumCast>(
     values: &[T; N],
@@ -22,6 +25,18 @@ pub fn lerp<T: Integer + NumCast + Copy, const N: usize, R: Num + NumCast>(
     num::cast(lerp).unwrap()
 }
 
+// the main thread is not allowed to block, so instead of waiting on a lock
+// the worker holds it spins until the worker lets go
+pub fn lock_on_main_thread<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    loop {
+        match mutex.try_lock() {
+            Ok(guard) => return guard,
+            Err(TryLockError::WouldBlock) => std::hint::spin_loop(),
+            Err(TryLockError::Poisoned(err)) => panic!("{err}"),
+        }
+    }
+}
+
 pub fn get_element_by_id<T: JsCast>(document: &Document, id: &str) -> T {
     document.get_element_by_id(id).unwrap().dyn_into().unwrap()
 }
@@ -58,6 +73,17 @@ pub async fn to_base64(file: File) -> String {
         .into()
 }
 
+pub async fn load_image_file(file: File) -> HtmlImageElement {
+    let src = to_base64(file).await;
+    let img = HtmlImageElement::new().unwrap();
+    img.set_src(&src);
+    let promise = Promise::new(&mut |resolve: Function, _reject: Function| {
+        img.set_onload(Some(&resolve));
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+    img
+}
+
 pub async fn worker_operation(worker: &Worker, operation_message: WorkerMessage) -> MainMessage {
     worker
         .post_message(&serde_wasm_bindgen::to_value(&operation_message).unwrap())
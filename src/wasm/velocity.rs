@@ -0,0 +1,112 @@
+use crate::{operators, paths::Cycle, pool};
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::TAU, ops::Range};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum VelocityProfile {
+    #[default]
+    Constant,
+    Ramp,
+    Sine,
+    Luminance,
+}
+
+impl VelocityProfile {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "constant" => Some(Self::Constant),
+            "ramp" => Some(Self::Ramp),
+            "sine" => Some(Self::Sine),
+            "luminance" => Some(Self::Luminance),
+            _ => None,
+        }
+    }
+
+    // splits every cycle of the path into segments that each rotate as a
+    // cycle of their own, so every frame stays a permutation of the pixels.
+    // about `segment_count` segments are spread over the whole path, and the
+    // speed of a segment is a fraction of the full speed between 0 and 1
+    pub fn segments(
+        self,
+        path: &[u32],
+        cycles: &[Cycle],
+        segment_count: u32,
+        original_pixel_data: &[u8],
+    ) -> Vec<VelocitySegment> {
+        let path_len = path.len();
+        let mut segments = Vec::new();
+        let mut cycle_start = 0;
+        for cycle in cycles {
+            let cycle_len = cycle.len as usize;
+            let cycle_segment_count = if self == Self::Constant {
+                1
+            } else {
+                ((segment_count as usize * cycle_len).div_ceil(path_len)).clamp(1, cycle_len)
+            };
+            for segment in 0..cycle_segment_count {
+                let range = pool::chunk_range(cycle_len, segment, cycle_segment_count);
+                let range = cycle_start + range.start..cycle_start + range.end;
+                let middle = (range.start + range.end) as f64 / 2.0 / path_len as f64;
+                let speed = match self {
+                    Self::Constant => 1.0,
+                    Self::Ramp => middle,
+                    Self::Sine => (1.0 - (middle * TAU * SINE_WAVES).cos()) / 2.0,
+                    Self::Luminance => {
+                        let total: u64 = path[range.clone()]
+                            .iter()
+                            .map(|&pixel| {
+                                let pixel = pixel as usize * 4;
+                                let rgba =
+                                    original_pixel_data[pixel..pixel + 4].try_into().unwrap();
+                                operators::luminance(u32::from_le_bytes(rgba)) as u64
+                            })
+                            .sum();
+                        total as f64 / range.len() as f64 / operators::luminance(u32::MAX) as f64
+                    }
+                };
+                // a segment at full speed keeps up with a rotation of its
+                // whole cycle, every segment moves at least a little
+                let revolutions = ((speed * cycle_segment_count as f64).round() as i64).max(1)
+                    * cycle.revolutions;
+                let phase = cycle.phase as usize % range.len();
+                segments.push(VelocitySegment {
+                    range,
+                    revolutions,
+                    phase,
+                });
+            }
+            cycle_start += cycle_len;
+        }
+        segments
+    }
+}
+
+const SINE_WAVES: f64 = 3.0;
+
+// a segment of the path turns `revolutions` times while the offset goes once
+// around the whole path, so the animation still loops after every offset.
+// segments with negative revolutions turn the other way
+#[derive(Clone, Debug)]
+pub struct VelocitySegment {
+    pub range: Range<usize>,
+    revolutions: i64,
+    phase: usize,
+}
+
+impl VelocitySegment {
+    pub fn offset(&self, offset: usize, path_len: usize) -> usize {
+        let len = self.range.len() as i128;
+        (offset as i128 * self.revolutions as i128 * len / path_len as i128 + self.phase as i128)
+            .rem_euclid(len) as usize
+    }
+
+    // the pixel at `idx` of the path moves back along the segment as far as
+    // the segment turns in `steps` steps from `offset`
+    pub fn index_after(&self, idx: usize, offset: usize, steps: i64, path_len: usize) -> usize {
+        let len = self.range.len();
+        let next_offset = (offset as i64 + steps).rem_euclid(path_len as i64) as usize;
+        let turned =
+            (self.offset(next_offset, path_len) + len - self.offset(offset, path_len)) % len;
+        self.range.start + (idx - self.range.start + len - turned) % len
+    }
+}
@@ -1,9 +1,16 @@
-use crate::{handlers, renderer};
+use crate::{
+    handlers,
+    operators::{Operator, Rearrangement},
+    paths::{self, Curve, Cycle, PathDiagnostics, PathIndex, Tiling},
+    pool, renderer,
+    velocity::{VelocityProfile, VelocitySegment},
+};
 use serde::{Deserialize, Serialize};
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    ops::Range,
     sync::{
-        atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicU8, Ordering},
         Mutex,
     },
     thread,
@@ -12,13 +19,75 @@ use wasm_bindgen::prelude::*;
 use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
 
 pub static PIXEL_DATA: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+pub static ORIGINAL_PIXEL_DATA: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+// pixels outside the mask stay where they are, no mask when empty
+pub static MASK: Mutex<Vec<bool>> = Mutex::new(Vec::new());
+pub static OFFSET: AtomicU32 = AtomicU32::new(0);
 pub static STOP_WORKER_LOOP: AtomicBool = AtomicBool::new(false);
+pub static STOP_ON_RESTORE: AtomicBool = AtomicBool::new(false);
+pub static RESTORE_COUNT: AtomicU32 = AtomicU32::new(0);
 pub static STEPS: AtomicI32 = AtomicI32::new(1);
 pub static SLEEP: AtomicU64 = AtomicU64::new(0);
+pub static ALPHA_MODE: AtomicU8 = AtomicU8::new(AlphaMode::Move as u8);
+pub static OPERATOR: AtomicU8 = AtomicU8::new(Operator::Rotate as u8);
+// red, green, blue and alpha each move this many times `STEPS`, they only
+// have a multiplier and no step of their own
+pub static CHANNEL_STEP_MULTIPLIERS: [AtomicI32; 4] = [
+    AtomicI32::new(1),
+    AtomicI32::new(1),
+    AtomicI32::new(1),
+    AtomicI32::new(1),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum AlphaMode {
+    Move,
+    Fixed,
+    Drop,
+}
+
+impl AlphaMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "move" => Some(Self::Move),
+            "fixed" => Some(Self::Fixed),
+            "drop" => Some(Self::Drop),
+            _ => None,
+        }
+    }
+
+    pub fn load() -> Self {
+        match ALPHA_MODE.load(Ordering::Relaxed) {
+            1 => Self::Fixed,
+            2 => Self::Drop,
+            _ => Self::Move,
+        }
+    }
+
+    // alpha only takes part in the rotation when it moves with the pixel
+    pub fn moved_channels(self) -> usize {
+        match self {
+            Self::Move => 4,
+            Self::Fixed | Self::Drop => 3,
+        }
+    }
+}
 
 #[derive(Default)]
 struct GlobalState {
-    path: RefCell<Vec<usize>>,
+    path_index: RefCell<Option<PathIndex>>,
+    cycles: RefCell<Vec<Cycle>>,
+    // channels without a curve of their own follow the path
+    channel_paths: RefCell<[Option<Vec<u32>>; 4]>,
+    // where the segments of the next rearranging step start
+    phase: Cell<usize>,
+    // hashes of the frame at offset 0 and of the current frame, when known
+    start_hash: Cell<Option<u64>>,
+    frame_hash: Cell<Option<u64>>,
+    velocity_profile: Cell<VelocityProfile>,
+    velocity_segment_count: Cell<u32>,
+    velocity_segments: RefCell<Vec<VelocitySegment>>,
 }
 
 #[wasm_bindgen(js_name = runWorker)]
@@ -36,29 +105,48 @@ pub fn run_worker() {
     closure.forget();
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "action", content = "payload")]
 pub enum WorkerMessage {
     Start,
     Step,
     LoadPath(LoadPathMessage),
+    Locate {
+        x: u32,
+        y: u32,
+    },
+    Seek {
+        offset: u32,
+    },
+    SetVelocity {
+        profile: VelocityProfile,
+        segment_count: u32,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct LoadPathMessage {
     width: u32,
     height: u32,
-    path_fn_ptr: usize,
+    curve: Curve,
+    channel_curves: [Option<Curve>; 4],
+    tiling: Tiling,
 }
 
-type PathFn = fn(u32, u32, u32) -> renderer::Point;
-
 impl LoadPathMessage {
-    pub fn new(width: u32, height: u32, path_fn: PathFn) -> Self {
+    pub fn new(
+        width: u32,
+        height: u32,
+        curve: Curve,
+        channel_curves: [Option<Curve>; 4],
+        tiling: Tiling,
+    ) -> Self {
         Self {
             width,
             height,
-            path_fn_ptr: path_fn as usize,
+            curve,
+            channel_curves,
+            tiling,
         }
     }
 }
@@ -67,12 +155,10 @@ impl WorkerMessage {
     fn process(self, global_state: &GlobalState) {
         match self {
             Self::Start => {
-                start(global_state);
+                let stopped_message = start(global_state);
                 js_sys::global()
                     .unchecked_into::<DedicatedWorkerGlobalScope>()
-                    .post_message(
-                        &serde_wasm_bindgen::to_value(&handlers::MainMessage::Stopped).unwrap(),
-                    )
+                    .post_message(&serde_wasm_bindgen::to_value(&stopped_message).unwrap())
                     .unwrap();
             }
             Self::Step => {
@@ -85,97 +171,428 @@ impl WorkerMessage {
                     .unwrap();
             }
             Self::LoadPath(load_path_message) => {
-                let path_len = load_path(load_path_message, global_state);
+                let loaded_path_message = match load_path(load_path_message, global_state) {
+                    Ok((path_len, cycle_count)) => handlers::MainMessage::LoadedPath {
+                        path_len,
+                        cycle_count,
+                    },
+                    Err(diagnostics) => handlers::MainMessage::InvalidPath(diagnostics),
+                };
+                js_sys::global()
+                    .unchecked_into::<DedicatedWorkerGlobalScope>()
+                    .post_message(&serde_wasm_bindgen::to_value(&loaded_path_message).unwrap())
+                    .unwrap();
+            }
+            Self::Seek { offset } => {
+                seek(offset, global_state);
+                js_sys::global()
+                    .unchecked_into::<DedicatedWorkerGlobalScope>()
+                    .post_message(
+                        &serde_wasm_bindgen::to_value(&handlers::MainMessage::Sought).unwrap(),
+                    )
+                    .unwrap();
+            }
+            Self::SetVelocity {
+                profile,
+                segment_count,
+            } => {
+                global_state.velocity_profile.set(profile);
+                global_state.velocity_segment_count.set(segment_count);
+                update_velocity_segments(global_state);
+                seek(OFFSET.load(Ordering::Relaxed), global_state);
                 js_sys::global()
                     .unchecked_into::<DedicatedWorkerGlobalScope>()
                     .post_message(
-                        &serde_wasm_bindgen::to_value(&handlers::MainMessage::LoadedPath {
-                            path_len,
-                        })
-                        .unwrap(),
+                        &serde_wasm_bindgen::to_value(&handlers::MainMessage::Sought).unwrap(),
                     )
                     .unwrap();
             }
+            Self::Locate { x, y } => {
+                let located_message = locate(x, y, global_state);
+                js_sys::global()
+                    .unchecked_into::<DedicatedWorkerGlobalScope>()
+                    .post_message(&serde_wasm_bindgen::to_value(&located_message).unwrap())
+                    .unwrap();
+            }
         }
     }
 }
 
-fn start(global_state: &GlobalState) {
+fn start(global_state: &GlobalState) -> handlers::MainMessage {
     loop {
-        step(global_state);
+        let restored = step(global_state);
+        if restored && STOP_ON_RESTORE.load(Ordering::Relaxed) {
+            STOP_WORKER_LOOP.store(false, Ordering::Relaxed);
+            return handlers::MainMessage::Restored;
+        }
         thread::sleep(std::time::Duration::from_micros(
             SLEEP.load(Ordering::Relaxed),
         ));
         if STOP_WORKER_LOOP.load(Ordering::Relaxed) {
             STOP_WORKER_LOOP.store(false, Ordering::Relaxed);
-            break;
+            return handlers::MainMessage::Stopped;
+        }
+    }
+}
+
+// returns whether the step brought back the original image
+fn step(global_state: &GlobalState) -> bool {
+    if let Some(rearrangement) = Operator::load().rearrangement() {
+        return rearrange(rearrangement, global_state);
+    }
+    let path_len = global_state
+        .path_index
+        .borrow()
+        .as_ref()
+        .map_or(0, PathIndex::len) as i64;
+    if path_len == 0 {
+        return false;
+    }
+    let steps = STEPS.load(Ordering::Relaxed) as i64;
+    let previous_offset = OFFSET.load(Ordering::Relaxed) as i64;
+    let offset = (previous_offset + steps).rem_euclid(path_len);
+    seek(offset as u32, global_state);
+    // every segment of every moved channel has to be back at its start, and
+    // a step that leaves the image as it was does not count
+    let moved_channels = AlphaMode::load().moved_channels();
+    let velocity_segments = global_state.velocity_segments.borrow();
+    let at_start = |offset: i64| {
+        channel_offsets(offset as usize, path_len as usize)[..moved_channels]
+            .iter()
+            .all(|&channel_offset| {
+                velocity_segments
+                    .iter()
+                    .all(|segment| segment.offset(channel_offset, path_len as usize) == 0)
+            })
+    };
+    let restored = at_start(offset) && !at_start(previous_offset);
+    if restored {
+        RESTORE_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    restored
+}
+
+// every other operator rearranges the current frame along the path, so
+// whether it is the original image again is found by comparing hashes
+fn rearrange(rearrangement: Rearrangement, global_state: &GlobalState) -> bool {
+    let path_index = global_state.path_index.borrow();
+    let path = path_index.as_ref().map_or(&[][..], PathIndex::pixels);
+    let steps = STEPS.load(Ordering::Relaxed).unsigned_abs() as usize;
+    if path.is_empty() || steps == 0 {
+        return false;
+    }
+    let steps = steps.max(2).min(path.len());
+    let phase = global_state.phase.get();
+    let pixel_data_ptr = PIXEL_DATA.lock().unwrap().as_mut_ptr() as usize;
+    rearrangement.apply(path, phase, steps, pixel_data_ptr, AlphaMode::load());
+    // segments straddle the previous ones on the next step
+    let segment_len = rearrangement.segment_len(steps);
+    global_state
+        .phase
+        .set((phase + (segment_len / 2).max(1)) % path.len());
+
+    let frame_hash = hash_pixel_data();
+    let restored = global_state.start_hash.get() == Some(frame_hash)
+        && global_state.frame_hash.get() != Some(frame_hash);
+    global_state.frame_hash.set(Some(frame_hash));
+    if restored {
+        RESTORE_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    restored
+}
+
+// hashed under the lock so the frame can't be replaced halfway through, the
+// main thread spins instead of blocking while it waits
+fn hash_pixel_data() -> u64 {
+    let pixel_data = PIXEL_DATA.lock().unwrap();
+    pixel_data.chunks_exact(4).fold(0, |hash: u64, pixel| {
+        let pixel = u32::from_le_bytes(pixel.try_into().unwrap());
+        (hash.rotate_left(5) ^ pixel as u64).wrapping_mul(0x517c_c1b7_2722_0a95)
+    })
+}
+
+// pixels outside the mask never move, but dropping the alpha still makes them
+// opaque
+fn reset_unmasked_pixels(alpha_mode: AlphaMode) {
+    let mask = MASK.lock().unwrap();
+    if mask.is_empty() {
+        return;
+    }
+    let mut pixel_data = PIXEL_DATA.lock().unwrap();
+    let original_pixel_data = ORIGINAL_PIXEL_DATA.lock().unwrap();
+    for ((rgba, original_rgba), _) in pixel_data
+        .chunks_exact_mut(4)
+        .zip(original_pixel_data.chunks_exact(4))
+        .zip(mask.iter())
+        .filter(|(_, &inside)| !inside)
+    {
+        rgba.copy_from_slice(original_rgba);
+        if alpha_mode == AlphaMode::Drop {
+            rgba[3] = u8::MAX;
         }
     }
 }
 
-fn step(global_state: &GlobalState) {
-    let mut path = global_state.path.borrow_mut();
+// every frame is gathered from the original pixels, the pixel at each index
+// of the path comes from `offset` indices further along it. the indices are
+// split between the pool workers since every one is written only once
+fn seek(offset: u32, global_state: &GlobalState) {
+    let path_index = global_state.path_index.borrow();
+    let path = path_index.as_ref().map_or(&[][..], PathIndex::pixels);
     let path_len = path.len();
-    let path_ptr = path.as_mut_ptr();
-    let pixel_data_ptr = PIXEL_DATA.lock().unwrap().as_mut_ptr();
-    let steps = STEPS.load(Ordering::Relaxed) as isize;
-    if steps > 0 {
-        let steps = steps as usize;
-        for path_index in 0..(path_len - steps) {
+    let offset = offset as usize % path_len.max(1);
+    OFFSET.store(offset as u32, Ordering::Relaxed);
+    let alpha_mode = AlphaMode::load();
+    reset_unmasked_pixels(alpha_mode);
+    if path_len == 0 {
+        return;
+    }
+    let moved_channels = alpha_mode.moved_channels();
+    let own_channel_paths = global_state.channel_paths.borrow();
+    let channel_paths = own_channel_paths
+        .each_ref()
+        .map(|channel_path| channel_path.as_deref().unwrap_or(path));
+    let channel_offsets = channel_offsets(offset, path_len);
+    let velocity_segments = global_state.velocity_segments.borrow();
+    // whole pixels can be moved at once while no channel goes its own way
+    if own_channel_paths.iter().all(Option::is_none)
+        && channel_offsets[..moved_channels]
+            .iter()
+            .all(|&channel_offset| channel_offset == offset)
+    {
+        gather_pixels(path, &velocity_segments, offset, alpha_mode);
+    } else {
+        gather_channels(
+            channel_paths,
+            &velocity_segments,
+            channel_offsets,
+            alpha_mode,
+        );
+    }
+    // the frame at offset 0 is where rearranging operators start from
+    let frame_hash = (offset == 0).then(hash_pixel_data);
+    global_state.frame_hash.set(frame_hash);
+    if offset == 0 {
+        global_state.start_hash.set(frame_hash);
+        global_state.phase.set(0);
+    }
+}
+
+// pairs up every index of `range` with the pixel it is gathered from, each
+// velocity segment rotating as a cycle of its own
+fn gather_sources<'a>(
+    path: &'a [u32],
+    velocity_segments: &'a [VelocitySegment],
+    range: Range<usize>,
+    offset: usize,
+) -> impl Iterator<Item = (&'a u32, &'a u32)> {
+    velocity_segments
+        .iter()
+        .filter(move |segment| segment.range.start < range.end && range.start < segment.range.end)
+        .flat_map(move |segment| {
+            let start = range.start.max(segment.range.start);
+            let end = range.end.min(segment.range.end);
+            let segment_path = &path[segment.range.clone()];
+            let first_source = (start - segment.range.start + segment.offset(offset, path.len()))
+                % segment_path.len();
+            let sources = segment_path[first_source..].iter().chain(segment_path);
+            path[start..end].iter().zip(sources)
+        })
+}
+
+fn gather_pixels(
+    path: &[u32],
+    velocity_segments: &[VelocitySegment],
+    offset: usize,
+    alpha_mode: AlphaMode,
+) {
+    let path_len = path.len();
+    let original_pixel_data_ptr = ORIGINAL_PIXEL_DATA.lock().unwrap().as_ptr() as usize;
+    let pixel_data_ptr = PIXEL_DATA.lock().unwrap().as_mut_ptr() as usize;
+    pool::run(&|chunk, chunks| {
+        let range = pool::chunk_range(path_len, chunk, chunks);
+        for (&destination, &source) in gather_sources(path, velocity_segments, range, offset) {
             unsafe {
-                swap_pixel(
-                    path_ptr.add(path_index),
-                    path_ptr.add((path_index + path_len - steps) % path_len),
-                    pixel_data_ptr,
+                copy_pixel(
+                    original_pixel_data_ptr as *const u32,
+                    pixel_data_ptr as *mut u32,
+                    source as usize,
+                    destination as usize,
+                    alpha_mode,
                 );
             }
         }
-    } else {
-        let steps = steps.unsigned_abs();
-        for path_index in (steps..path_len).rev() {
-            unsafe {
-                swap_pixel(
-                    path_ptr.add(path_index),
-                    path_ptr.add((path_index + path_len - steps) % path_len),
-                    pixel_data_ptr,
-                );
+    });
+}
+
+// every channel is gathered along its own path, a channel that is not moved
+// is filled in per pixel afterwards
+fn gather_channels(
+    channel_paths: [&[u32]; 4],
+    velocity_segments: &[VelocitySegment],
+    channel_offsets: [usize; 4],
+    alpha_mode: AlphaMode,
+) {
+    let path_len = channel_paths[0].len();
+    let moved_channels = alpha_mode.moved_channels();
+    let original_pixel_data_ptr = ORIGINAL_PIXEL_DATA.lock().unwrap().as_ptr() as usize;
+    let pixel_data_ptr = PIXEL_DATA.lock().unwrap().as_mut_ptr() as usize;
+    pool::run(&|chunk, chunks| {
+        let original_pixel_data_ptr = original_pixel_data_ptr as *const u8;
+        let pixel_data_ptr = pixel_data_ptr as *mut u8;
+        let range = pool::chunk_range(path_len, chunk, chunks);
+        for channel in 0..moved_channels {
+            let sources = gather_sources(
+                channel_paths[channel],
+                velocity_segments,
+                range.clone(),
+                channel_offsets[channel],
+            );
+            for (&destination, &source) in sources {
+                unsafe {
+                    *pixel_data_ptr.add(destination as usize * 4 + channel) =
+                        *original_pixel_data_ptr.add(source as usize * 4 + channel);
+                }
             }
         }
-    }
+        if alpha_mode != AlphaMode::Move {
+            for &pixel in &channel_paths[0][range] {
+                let pixel = pixel as usize;
+                unsafe {
+                    *pixel_data_ptr.add(pixel * 4 + 3) = match alpha_mode {
+                        AlphaMode::Fixed => *original_pixel_data_ptr.add(pixel * 4 + 3),
+                        _ => u8::MAX,
+                    };
+                }
+            }
+        }
+    });
 }
 
-unsafe fn swap_pixel(
-    first_pixel_ptr: *mut usize,
-    second_pixel_ptr: *mut usize,
-    pixel_data_ptr: *mut u8,
+// each channel moves its multiple of the offset along its path
+fn channel_offsets(offset: usize, path_len: usize) -> [usize; 4] {
+    CHANNEL_STEP_MULTIPLIERS.each_ref().map(|multiplier| {
+        (offset as i64 * multiplier.load(Ordering::Relaxed) as i64).rem_euclid(path_len as i64)
+            as usize
+    })
+}
+
+// pixels are moved as whole rgba words, the canvas buffer is not guaranteed
+// to be aligned to them. alpha is the high byte
+pub const ALPHA_MASK: u32 = 0xff00_0000;
+
+unsafe fn copy_pixel(
+    original_pixel_data_ptr: *const u32,
+    pixel_data_ptr: *mut u32,
+    source: usize,
+    destination: usize,
+    alpha_mode: AlphaMode,
 ) {
-    core::ptr::swap(
-        pixel_data_ptr.add(*first_pixel_ptr),
-        pixel_data_ptr.add(*second_pixel_ptr),
-    );
-    core::ptr::swap(
-        pixel_data_ptr.add(*first_pixel_ptr + 1),
-        pixel_data_ptr.add(*second_pixel_ptr + 1),
-    );
-    core::ptr::swap(
-        pixel_data_ptr.add(*first_pixel_ptr + 2),
-        pixel_data_ptr.add(*second_pixel_ptr + 2),
-    );
+    let pixel = original_pixel_data_ptr.add(source).read_unaligned();
+    let pixel = match alpha_mode {
+        AlphaMode::Move => pixel,
+        AlphaMode::Fixed => {
+            let original_pixel = original_pixel_data_ptr.add(destination).read_unaligned();
+            (original_pixel & ALPHA_MASK) | (pixel & !ALPHA_MASK)
+        }
+        AlphaMode::Drop => pixel | ALPHA_MASK,
+    };
+    pixel_data_ptr.add(destination).write_unaligned(pixel);
 }
 
-fn load_path(load_path_message: LoadPathMessage, global_state: &GlobalState) -> u32 {
-    let path_fn: PathFn = unsafe { std::mem::transmute(load_path_message.path_fn_ptr) };
-    let mut path: Vec<_> = (0..(load_path_message.width * load_path_message.height))
-        .map(|idx| path_fn(idx, load_path_message.width, load_path_message.height))
-        .map(|renderer::Point(x, y)| {
-            (y.rem_euclid(load_path_message.height as i32) as usize
-                * load_path_message.width as usize
-                + x.rem_euclid(load_path_message.width as i32) as usize)
-                * 4
+fn load_path(
+    load_path_message: LoadPathMessage,
+    global_state: &GlobalState,
+) -> Result<(u32, u32), PathDiagnostics> {
+    let LoadPathMessage {
+        width,
+        height,
+        curve,
+        channel_curves,
+        tiling,
+    } = load_path_message;
+    // curves sorted by colour follow the original pixels, not the frame shown
+    let original_pixel_data = ORIGINAL_PIXEL_DATA.lock().unwrap();
+    let pixels = paths::Pixels::new(&original_pixel_data, width);
+    let points = tiling.points(&curve, width, height, pixels);
+    let channel_points = channel_curves.map(|channel_curve| {
+        channel_curve.map(|curve| tiling.points(&curve, width, height, pixels))
+    });
+    drop(original_pixel_data);
+    let validated = paths::validate(&points, width, height).and_then(|()| {
+        channel_points
+            .iter()
+            .flatten()
+            .try_for_each(|points| paths::validate(points, width, height))
+    });
+    OFFSET.store(0, Ordering::Relaxed);
+    RESTORE_COUNT.store(0, Ordering::Relaxed);
+    PIXEL_DATA
+        .lock()
+        .unwrap()
+        .copy_from_slice(&ORIGINAL_PIXEL_DATA.lock().unwrap());
+    let mut path_index = global_state.path_index.borrow_mut();
+    let mut channel_paths = global_state.channel_paths.borrow_mut();
+    if let Err(diagnostics) = validated {
+        *path_index = None;
+        *channel_paths = Default::default();
+        return Err(diagnostics);
+    }
+    // the whole curve is checked before the mask takes pixels out of it
+    let mask = MASK.lock().unwrap();
+    let cycles = tiling.cycles(&curve, &points, width, height);
+    let (points, cycles) = paths::masked(points, &cycles, &mask, width);
+    let new_path_index = PathIndex::new(&points, width, height);
+    let path_len = new_path_index.len();
+    *path_index = Some(new_path_index);
+    let cycle_count = cycles.len() as u32;
+    *global_state.cycles.borrow_mut() = cycles;
+    // channels are only ever gathered, they need no lookup of their own
+    *channel_paths = channel_points.map(|channel_points| {
+        channel_points.map(|points| {
+            let (points, _) = paths::masked(points, &[], &mask, width);
+            paths::pixel_indices(&points, width)
         })
-        .collect();
-    path.dedup();
-    let path_len = path.len();
-    *global_state.path.borrow_mut() = path;
-    path_len as u32
+    });
+    drop(mask);
+    drop(path_index);
+    drop(channel_paths);
+    update_velocity_segments(global_state);
+    // the first frame depends on the alpha mode too
+    seek(0, global_state);
+    Ok((path_len, cycle_count))
+}
+
+fn update_velocity_segments(global_state: &GlobalState) {
+    let path_index = global_state.path_index.borrow();
+    let path = path_index.as_ref().map_or(&[][..], PathIndex::pixels);
+    *global_state.velocity_segments.borrow_mut() = global_state.velocity_profile.get().segments(
+        path,
+        &global_state.cycles.borrow(),
+        global_state.velocity_segment_count.get(),
+        &ORIGINAL_PIXEL_DATA.lock().unwrap(),
+    );
+}
+
+fn locate(x: u32, y: u32, global_state: &GlobalState) -> handlers::MainMessage {
+    let path_index = global_state.path_index.borrow();
+    let index = path_index
+        .as_ref()
+        .and_then(|path_index| path_index.index_of(x, y));
+    // only a rotation moves a pixel to a known place
+    let rotating = Operator::load() == Operator::Rotate;
+    let next = path_index
+        .as_ref()
+        .filter(|_| rotating)
+        .and_then(|path_index| {
+            let renderer::Point(x, y) = path_index.position_after(
+                x,
+                y,
+                OFFSET.load(Ordering::Relaxed) as usize,
+                STEPS.load(Ordering::Relaxed) as i64,
+                &global_state.velocity_segments.borrow(),
+            )?;
+            Some((x, y))
+        });
+    handlers::MainMessage::Located { index, next }
 }
@@ -1,5 +1,10 @@
-import { initSync, runWorker } from "./pkg/image_curve.js";
+import { initSync, runWorker, runPoolWorker } from "./pkg/image_curve.js";
 self.onmessage = (e) => {
-  initSync(...e.data);
-  runWorker();
+  const [module, memory, role] = e.data;
+  initSync(module, memory);
+  if (role === "pool") {
+    runPoolWorker();
+  } else {
+    runWorker();
+  }
 };